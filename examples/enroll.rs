@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 
-use libfprint_rs::{FpContext, FpDevice, FpFinger, FpPrint};
+use libfprint_rs::{EnrollTemplate, FpContext, FpDevice, FpFinger, FpPrint};
 
 fn main() {
     // Get context
@@ -15,9 +15,11 @@ fn main() {
     dev.open_sync(None).unwrap();
 
     // Create a template print
-    let template = FpPrint::new(dev);
-    template.set_finger(FpFinger::RightRing);
-    template.set_username("test");
+    let template = EnrollTemplate::builder(dev)
+        .finger(FpFinger::RightRing)
+        .username("test")
+        .build()
+        .unwrap();
 
     // User data that we will use on the callback function,
     // to mutate the value of a counter, it must be wrapped in an Arc<Mutex<T>>
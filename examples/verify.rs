@@ -1,4 +1,4 @@
-use libfprint_rs::{FpContext, FpDevice, FpPrint};
+use libfprint_rs::{EnrollTemplate, FpContext, FpDevice, FpFinger, FpPrint};
 
 fn main() {
     // Get devices
@@ -8,7 +8,11 @@ fn main() {
     dev.open_sync(None).unwrap();
 
     // Create a template print
-    let template = FpPrint::new(dev);
+    let template = EnrollTemplate::builder(dev)
+        .finger(FpFinger::RightIndex)
+        .username("test")
+        .build()
+        .unwrap();
     let enrolled_print = dev
         .enroll_sync(template, None, Some(progress_cb), None)
         .unwrap();
@@ -44,7 +48,7 @@ pub fn progress_cb(
 pub fn match_cb(
     _device: &FpDevice,
     matched_print: Option<FpPrint>,
-    _print: FpPrint,
+    _print: Option<FpPrint>,
     _error: Option<glib::Error>,
     _data: &Option<()>,
 ) {
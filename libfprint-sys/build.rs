@@ -8,6 +8,7 @@ fn main() {
         .allowlist_function("fp_print.*")
         .allowlist_function("fp_context.*")
         .allowlist_function("fp_image.*")
+        .allowlist_function("fp_minutia.*")
         .allowlist_function("g_ptr_array_free")
         // GObject types and their get_type functions
         .allowlist_type("FpContextClass")
@@ -22,6 +23,7 @@ fn main() {
         .allowlist_function("fp_context_get_type")
         .allowlist_function("fp_device_get_type")
         .allowlist_function("fp_image_get_type")
+        .allowlist_function("fp_image_device_get_type")
         .allowlist_function("fp_print_get_type")
         // Other types
         .allowlist_type("FpPrint_autoptr")
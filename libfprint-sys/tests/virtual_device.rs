@@ -0,0 +1,91 @@
+//! Exercises the feature and suspend/resume entry points through libfprint's `virtual_device` driver, so they are
+//! covered on every target the crate is built for, not only on x86. CI runs it on x86_64 and aarch64.
+use std::ffi::CStr;
+
+use libfprint_sys::*;
+
+/// Returns the `virtual_device` device of `context`, if libfprint was built with the virtual drivers.
+unsafe fn find_virtual_device(context: *mut FpContext) -> Option<*mut FpDevice> {
+    let devices = unsafe { &*fp_context_get_devices(context) };
+    (0..devices.len as usize)
+        .map(|i| unsafe { *devices.pdata.add(i) } as *mut FpDevice)
+        .find(|&device| {
+            let driver = unsafe { CStr::from_ptr(fp_device_get_driver(device)) };
+            driver.to_bytes() == b"virtual_device"
+        })
+}
+
+/// Asserts that a `gboolean` returning call succeeded, or failed only because the driver does not implement it.
+unsafe fn assert_ok_or_not_supported(res: gboolean, error: *mut GError) {
+    if res == 0 {
+        let error = unsafe { &*error };
+        let message = unsafe { CStr::from_ptr(error.message) };
+        assert_eq!(
+            error.domain,
+            unsafe { fp_device_error_quark() },
+            "{:?}",
+            message
+        );
+        assert_eq!(
+            error.code as FpDeviceError, FpDeviceError_FP_DEVICE_ERROR_NOT_SUPPORTED,
+            "{:?}",
+            message
+        );
+    }
+}
+
+#[test]
+fn features_and_suspend() {
+    let socket = std::env::temp_dir().join(format!("libfprint-sys-{}.socket", std::process::id()));
+    // Safety: this is the only test of this binary, no other thread reads the environment.
+    unsafe { std::env::set_var("FP_VIRTUAL_DEVICE", &socket) };
+
+    unsafe {
+        let context = fp_context_new();
+        let Some(device) = find_virtual_device(context) else {
+            // CI sets FP_VIRTUAL_REQUIRED so that a libfprint without the driver does not pass silently
+            assert!(
+                std::env::var_os("FP_VIRTUAL_REQUIRED").is_none(),
+                "libfprint was built without the virtual_device driver"
+            );
+            eprintln!(
+                "libfprint was built without the virtual_device driver, skipping. Set FP_VIRTUAL_REQUIRED=1 to fail instead"
+            );
+            return;
+        };
+
+        let mut error = std::ptr::null_mut();
+        assert_ne!(
+            fp_device_open_sync(device, std::ptr::null_mut(), &mut error),
+            0
+        );
+
+        let features = fp_device_get_features(device);
+        assert_ne!(features & FpDeviceFeature_FP_DEVICE_FEATURE_VERIFY, 0);
+        assert_ne!(features & FpDeviceFeature_FP_DEVICE_FEATURE_IDENTIFY, 0);
+        assert_ne!(
+            fp_device_has_feature(device, FpDeviceFeature_FP_DEVICE_FEATURE_VERIFY),
+            0
+        );
+        assert_eq!(
+            fp_device_has_feature(device, features),
+            1,
+            "The device must support all of its own features"
+        );
+
+        let res = fp_device_suspend_sync(device, std::ptr::null_mut(), &mut error);
+        assert_ok_or_not_supported(res, error);
+        if res != 0 {
+            assert_ne!(
+                fp_device_resume_sync(device, std::ptr::null_mut(), &mut error),
+                0
+            );
+        }
+
+        assert_ne!(
+            fp_device_close_sync(device, std::ptr::null_mut(), &mut error),
+            0
+        );
+    }
+    let _ = std::fs::remove_file(socket);
+}
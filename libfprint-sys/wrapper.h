@@ -9,4 +9,5 @@ typedef FpPrint *FpPrint_autoptr;
 GType fp_context_get_type(void);
 GType fp_device_get_type(void);
 GType fp_image_get_type(void);
+GType fp_image_device_get_type(void);
 GType fp_print_get_type(void);
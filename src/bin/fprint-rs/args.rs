@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+
+use libfprint_rs::FpFinger;
+
+pub const USAGE: &str = "\
+Usage: fprint-rs [--json] [--device <index|id>] <command> [options]
+
+Commands:
+  devices                                  List the fingerprint readers
+  enroll --finger <finger> [--user <name>] -o <file>
+                                           Enroll a finger and save the print to <file>
+  verify <file>                            Verify a finger against the print in <file>
+  identify <dir>                           Identify a finger among the prints in <dir>
+  capture -o <image.pgm>                   Capture a fingerprint image
+  list                                     List the prints stored on the device
+  delete <file> | --finger <finger> [--user <name>]
+                                           Delete a print from the device storage
+  clear                                    Delete all the prints from the device storage
+
+Options:
+  --json                  Print the results as JSON
+  --device <index|id>     Use the given device instead of the first one
+  -h, --help              Print this help
+
+Fingers are named like `right-index` or `left-thumb`. Enrolled prints belong to the current user unless --user
+is given.
+
+Exit codes: 0 on success or match, 1 on no match, 2 on error.";
+
+/// Parsed command line.
+pub struct Args {
+    pub json: bool,
+    pub device: Option<String>,
+    pub command: Command,
+}
+
+pub enum Command {
+    Help,
+    Devices,
+    Enroll {
+        finger: FpFinger,
+        user: Option<String>,
+        output: PathBuf,
+    },
+    Verify {
+        file: PathBuf,
+    },
+    Identify {
+        dir: PathBuf,
+    },
+    Capture {
+        output: PathBuf,
+    },
+    List,
+    Delete {
+        file: Option<PathBuf>,
+        finger: Option<FpFinger>,
+        user: Option<String>,
+    },
+    Clear,
+}
+
+/// Options given to a command, before they are checked against what the command accepts.
+#[derive(Default)]
+struct Options {
+    finger: Option<FpFinger>,
+    user: Option<String>,
+    output: Option<PathBuf>,
+    positional: Vec<String>,
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut json = false;
+    let mut device = None;
+    let mut command = None;
+    let mut options = Options::default();
+    let mut given = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => command = Some("help".to_string()),
+            "--json" => json = true,
+            "--device" => device = Some(value(&arg)?),
+            "--finger" => options.finger = Some(parse_finger(&value(&arg)?)?),
+            "--user" => options.user = Some(value(&arg)?),
+            "-o" | "--output" => options.output = Some(value(&arg)?.into()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if command.is_none() => {
+                command = Some(arg);
+                continue;
+            }
+            _ => {
+                options.positional.push(arg);
+                continue;
+            }
+        }
+        given.push(arg);
+    }
+
+    let command = command.ok_or("Missing command")?;
+    let accepts = |allowed: &[&str], positional: usize| -> Result<(), String> {
+        let option = given.iter().find(|arg| {
+            !matches!(arg.as_str(), "--json" | "--device" | "-h" | "--help")
+                && !allowed.contains(&arg.as_str())
+        });
+        if let Some(option) = option {
+            return Err(format!("{} does not accept {}", command, option));
+        }
+        if options.positional.len() > positional {
+            return Err(format!("Too many arguments for {}", command));
+        }
+        Ok(())
+    };
+
+    let command = match command.as_str() {
+        "help" => Command::Help,
+        "devices" => {
+            accepts(&[], 0)?;
+            Command::Devices
+        }
+        "enroll" => {
+            accepts(&["--finger", "--user", "-o", "--output"], 0)?;
+            Command::Enroll {
+                finger: options.finger.ok_or("enroll requires --finger")?,
+                user: options.user,
+                output: options.output.ok_or("enroll requires -o <file>")?,
+            }
+        }
+        "verify" => {
+            accepts(&[], 1)?;
+            Command::Verify {
+                file: options
+                    .positional
+                    .pop()
+                    .ok_or("verify requires a file")?
+                    .into(),
+            }
+        }
+        "identify" => {
+            accepts(&[], 1)?;
+            Command::Identify {
+                dir: options
+                    .positional
+                    .pop()
+                    .ok_or("identify requires a directory")?
+                    .into(),
+            }
+        }
+        "capture" => {
+            accepts(&["-o", "--output"], 0)?;
+            Command::Capture {
+                output: options.output.ok_or("capture requires -o <image.pgm>")?,
+            }
+        }
+        "list" => {
+            accepts(&[], 0)?;
+            Command::List
+        }
+        "delete" => {
+            accepts(&["--finger", "--user"], 1)?;
+            let file = options.positional.pop().map(PathBuf::from);
+            if file.is_some() == options.finger.is_some() {
+                return Err("delete requires either a file or --finger".to_string());
+            }
+            Command::Delete {
+                file,
+                finger: options.finger,
+                user: options.user,
+            }
+        }
+        "clear" => {
+            accepts(&[], 0)?;
+            Command::Clear
+        }
+        _ => return Err(format!("Unknown command {}", command)),
+    };
+
+    Ok(Args {
+        json,
+        device,
+        command,
+    })
+}
+
+/// Parses finger names like `right-index`, also accepting fprintd's `right-index-finger`.
+pub fn parse_finger(name: &str) -> Result<FpFinger, String> {
+    let finger = match name.trim_end_matches("-finger") {
+        "left-thumb" => FpFinger::LeftThumb,
+        "left-index" => FpFinger::LeftIndex,
+        "left-middle" => FpFinger::LeftMiddle,
+        "left-ring" => FpFinger::LeftRing,
+        "left-little" => FpFinger::LeftLittle,
+        "right-thumb" => FpFinger::RightThumb,
+        "right-index" => FpFinger::RightIndex,
+        "right-middle" => FpFinger::RightMiddle,
+        "right-ring" => FpFinger::RightRing,
+        "right-little" => FpFinger::RightLittle,
+        _ => return Err(format!("Unknown finger {}", name)),
+    };
+    Ok(finger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Args, String> {
+        parse(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn options_per_command() {
+        let args =
+            parse_line("--json --device 1 enroll --finger right-index --user bruce -o print")
+                .unwrap();
+        assert!(args.json);
+        assert_eq!(args.device.as_deref(), Some("1"));
+        let Command::Enroll {
+            finger,
+            user,
+            output,
+        } = args.command
+        else {
+            panic!("expected enroll");
+        };
+        assert_eq!(finger, FpFinger::RightIndex);
+        assert_eq!(user.as_deref(), Some("bruce"));
+        assert_eq!(output, PathBuf::from("print"));
+
+        assert!(matches!(
+            parse_line("verify print").unwrap().command,
+            Command::Verify { .. }
+        ));
+        assert!(matches!(
+            parse_line("capture -o image.pgm").unwrap().command,
+            Command::Capture { .. }
+        ));
+        assert!(matches!(
+            parse_line("--help").unwrap().command,
+            Command::Help
+        ));
+
+        assert!(parse_line("enroll -o print").is_err());
+        assert!(parse_line("enroll --finger right-index").is_err());
+        assert!(parse_line("verify print --finger right-index").is_err());
+        assert!(parse_line("verify a b").is_err());
+        assert!(parse_line("list --user bruce").is_err());
+        assert!(parse_line("clear extra").is_err());
+        assert!(parse_line("list --unknown").is_err());
+        assert!(parse_line("enroll --finger").is_err());
+        assert!(parse_line("frobnicate").is_err());
+        assert!(parse_line("--json").is_err());
+    }
+
+    #[test]
+    fn delete_file_or_finger() {
+        let Command::Delete { file, finger, .. } = parse_line("delete print").unwrap().command
+        else {
+            panic!("expected delete");
+        };
+        assert_eq!((file, finger), (Some(PathBuf::from("print")), None));
+
+        let Command::Delete { file, finger, user } =
+            parse_line("delete --finger left-thumb --user bruce")
+                .unwrap()
+                .command
+        else {
+            panic!("expected delete");
+        };
+        assert_eq!((file, finger), (None, Some(FpFinger::LeftThumb)));
+        assert_eq!(user.as_deref(), Some("bruce"));
+
+        assert!(parse_line("delete").is_err());
+        assert!(parse_line("delete print --finger left-thumb").is_err());
+    }
+
+    #[test]
+    fn finger_names() {
+        assert_eq!(parse_finger("right-index"), Ok(FpFinger::RightIndex));
+        assert_eq!(parse_finger("right-index-finger"), Ok(FpFinger::RightIndex));
+        assert_eq!(parse_finger("left-little-finger"), Ok(FpFinger::LeftLittle));
+        assert!(parse_finger("right-toe").is_err());
+        assert!(parse_finger("").is_err());
+    }
+}
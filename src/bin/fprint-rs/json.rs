@@ -0,0 +1,185 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// Minimal JSON value, enough to describe the command results.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    pub fn object<const N: usize>(fields: [(&'static str, Json); N]) -> Self {
+        Json::Object(fields.into())
+    }
+
+    /// Human readable rendering, used when `--json` is not given.
+    pub fn write_text(&self, out: &mut String, indent: usize) {
+        match self {
+            Json::Object(fields) => {
+                for (key, value) in fields {
+                    out.push_str(&"  ".repeat(indent));
+                    out.push_str(key);
+                    out.push(':');
+                    match value {
+                        Json::Object(_) | Json::Array(_) => {
+                            out.push('\n');
+                            value.write_text(out, indent + 1);
+                        }
+                        _ => {
+                            out.push(' ');
+                            value.write_text(out, indent);
+                            out.push('\n');
+                        }
+                    }
+                }
+            }
+            Json::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                    }
+                    match item {
+                        Json::Object(_) | Json::Array(_) => item.write_text(out, indent),
+                        _ => {
+                            out.push_str(&"  ".repeat(indent));
+                            item.write_text(out, indent);
+                            out.push('\n');
+                        }
+                    }
+                }
+            }
+            Json::Null => out.push('-'),
+            Json::Bool(value) => out.push_str(if *value { "yes" } else { "no" }),
+            Json::Number(value) => out.push_str(&value.to_string()),
+            Json::String(value) => out.push_str(value),
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(Json::from("plain").to_string(), r#""plain""#);
+        assert_eq!(Json::from(r#"a "b" \ c"#).to_string(), r#""a \"b\" \\ c""#);
+        assert_eq!(Json::from("a\nb\r\t").to_string(), r#""a\nb\r\t""#);
+        assert_eq!(Json::from("\u{1}\u{1f}").to_string(), r#""\u0001\u001f""#);
+        assert_eq!(Json::from("é ✓").to_string(), "\"é ✓\"");
+    }
+
+    #[test]
+    fn control_and_unicode() {
+        // Every control character below U+0020 must be escaped, the others are valid as is in UTF-8 JSON
+        assert_eq!(
+            Json::from("\0\u{8}\u{c}\u{1b}").to_string(),
+            r#""\u0000\u0008\u000c\u001b""#
+        );
+        assert_eq!(Json::from("\u{7f}").to_string(), "\"\u{7f}\"");
+        assert_eq!(
+            Json::from("\u{2028}\u{2029}").to_string(),
+            "\"\u{2028}\u{2029}\""
+        );
+        assert_eq!(Json::from("Zoë 指紋 🖐").to_string(), "\"Zoë 指紋 🖐\"");
+        let key = Json::object([("user", "a\u{0}b".into())]);
+        assert_eq!(key.to_string(), r#"{"user":"a\u0000b"}"#);
+    }
+
+    #[test]
+    fn objects() {
+        let value = Json::object([
+            ("matched", true.into()),
+            ("file", None::<String>.into()),
+            (
+                "stages",
+                Json::Array(vec![Json::Number(1), Json::Number(2)]),
+            ),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"matched":true,"file":null,"stages":[1,2]}"#
+        );
+    }
+}
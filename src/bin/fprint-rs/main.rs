@@ -0,0 +1,379 @@
+//! Command line tool to manage fingerprint readers, see `fprint-rs --help`.
+mod args;
+mod json;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use glib::translate::IntoGlib;
+use libfprint_rs::{
+    EnrollTemplate, FpContext, FpDevice, FpError, FpFinger, FpMatchCb, FpPrint, GDate, Gallery,
+    current_user,
+};
+
+use args::{Args, Command};
+use json::Json;
+
+/// Exit code of a successful command, or of a match.
+const EXIT_OK: u8 = 0;
+/// Exit code of a verify or identify without a match.
+const EXIT_NO_MATCH: u8 = 1;
+/// Exit code of a failed command.
+const EXIT_ERROR: u8 = 2;
+
+/// Error of a command, with the `FpError` variant name when it comes from the device.
+struct Error {
+    kind: &'static str,
+    message: String,
+}
+
+impl From<FpError> for Error {
+    fn from(error: FpError) -> Self {
+        let kind = match &error {
+            FpError::General(_) => "general",
+            FpError::NotSupported(_) => "not-supported",
+            FpError::NotOpen(_) => "not-open",
+            FpError::AlreadyOpen(_) => "already-open",
+            FpError::Busy(_) => "busy",
+            FpError::Proto(_) => "proto",
+            FpError::DataInvalid(_) => "data-invalid",
+            FpError::DataNotFound(_) => "data-not-found",
+            FpError::DataFull(_) => "data-full",
+            FpError::DataDuplicate(_) => "data-duplicate",
+            FpError::Removed(_) => "removed",
+            FpError::TooHot(_) => "too-hot",
+            FpError::RetryGeneral(_)
+            | FpError::RetryTooShort(_)
+            | FpError::RetryCenterFinger(_)
+            | FpError::RetryRemoveFinger(_) => "retry",
+            FpError::Cancelled(_) => "cancelled",
+            FpError::Other(_) => "other",
+        };
+        Error {
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<glib::Error> for Error {
+    fn from(error: glib::Error) -> Self {
+        FpError::from(error).into()
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error {
+            kind: "io",
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error {
+            kind: "usage",
+            message,
+        }
+    }
+}
+
+/// Result of a command: the report to print and the exit code.
+type Outcome = Result<(Json, u8), Error>;
+
+fn main() -> ExitCode {
+    let args = match args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, args::USAGE);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    if let Command::Help = args.command {
+        println!("{}", args::USAGE);
+        return ExitCode::from(EXIT_OK);
+    }
+    let json = args.json;
+
+    let outcome = run(args);
+    let code = exit_code(&outcome);
+    let report = match outcome {
+        Ok((report, _)) => report,
+        Err(error) => {
+            if !json {
+                eprintln!("Error: {}", error.message);
+                return ExitCode::from(code);
+            }
+            Json::object([
+                ("error", error.kind.into()),
+                ("message", error.message.into()),
+            ])
+        }
+    };
+
+    if json {
+        println!("{}", report);
+    } else {
+        let mut text = String::new();
+        report.write_text(&mut text, 0);
+        print!("{}", text);
+    }
+    ExitCode::from(code)
+}
+
+/// Exit code of the outcome of a command.
+fn exit_code(outcome: &Outcome) -> u8 {
+    match outcome {
+        Ok((_, code)) => *code,
+        Err(_) => EXIT_ERROR,
+    }
+}
+
+/// Exit code of a verify or identify.
+fn match_code(matched: bool) -> u8 {
+    if matched { EXIT_OK } else { EXIT_NO_MATCH }
+}
+
+fn run(args: Args) -> Outcome {
+    let context = FpContext::new();
+    let devices = context.devices();
+    if let Command::Devices = args.command {
+        let devices = devices.iter().map(describe_device).collect();
+        return Ok((Json::Array(devices), EXIT_OK));
+    }
+
+    let dev = select_device(&devices, args.device.as_deref())?;
+    dev.open_sync(None)?;
+    let outcome = match args.command {
+        Command::Help | Command::Devices => unreachable!(),
+        Command::Enroll {
+            finger,
+            user,
+            output,
+        } => enroll(dev, finger, user, &output),
+        Command::Verify { file } => verify(dev, &file),
+        Command::Identify { dir } => identify(dev, &dir),
+        Command::Capture { output } => capture(dev, &output),
+        Command::List => list(dev),
+        Command::Delete { file, finger, user } => delete(dev, file, finger, user),
+        Command::Clear => {
+            dev.clear_storage_sync(None)?;
+            Ok((Json::object([("cleared", true.into())]), EXIT_OK))
+        }
+    };
+    // Report the failure of the command rather than the one of closing the device
+    let _ = dev.close_sync(None);
+    outcome
+}
+
+fn select_device<'a>(
+    devices: &'a [FpDevice],
+    selector: Option<&str>,
+) -> Result<&'a FpDevice, Error> {
+    let dev = match selector {
+        None => devices.first(),
+        Some(selector) => match selector.parse::<usize>() {
+            Ok(index) => devices.get(index),
+            Err(_) => devices.iter().find(|dev| dev.device_id() == selector),
+        },
+    };
+    dev.ok_or_else(|| Error {
+        kind: "no-device",
+        message: "No such fingerprint reader".to_string(),
+    })
+}
+
+fn describe_device(dev: &FpDevice) -> Json {
+    Json::object([
+        ("name", dev.name().into()),
+        ("driver", dev.driver().into()),
+        ("id", dev.device_id().into()),
+        (
+            "scan_type",
+            format!("{:?}", dev.scan_type()).to_lowercase().into(),
+        ),
+        (
+            "features",
+            Json::Array(
+                dev.features()
+                    .iter_names()
+                    .map(|(name, _)| name.to_lowercase().replace('_', "-").into())
+                    .collect(),
+            ),
+        ),
+        ("enroll_stages", i64::from(dev.nr_enroll_stage()).into()),
+    ])
+}
+
+fn describe_print(print: &FpPrint) -> Json {
+    let date = print.enroll_date().filter(GDate::valid).map(|date| {
+        format!(
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month().into_glib(),
+            date.day()
+        )
+    });
+    Json::object([
+        ("finger", finger_name(print.finger()).into()),
+        ("user", print.username().into()),
+        ("description", print.description().into()),
+        ("enroll_date", date.into()),
+        ("device_stored", print.device_stored().into()),
+    ])
+}
+
+fn finger_name(finger: FpFinger) -> Option<String> {
+    (finger != FpFinger::Unknown).then(|| finger.to_string().to_lowercase().replace(' ', "-"))
+}
+
+fn read_print(file: &Path) -> Result<FpPrint, Error> {
+    let data = std::fs::read(file)?;
+    Ok(FpPrint::deserialize(&data)?)
+}
+
+fn enroll(dev: &FpDevice, finger: FpFinger, user: Option<String>, output: &Path) -> Outcome {
+    let mut builder = EnrollTemplate::builder(dev).finger(finger);
+    // Prints belong to the effective user unless another one is given
+    if let Some(user) = user.or_else(current_user) {
+        builder = builder.username(&user);
+    }
+    let template = builder.build()?;
+
+    let total = dev.nr_enroll_stage();
+    let progress = move |_: &FpDevice,
+                         stage: i32,
+                         _: Option<FpPrint>,
+                         error: Option<glib::Error>,
+                         _: &Option<()>| match error {
+        Some(error) => eprintln!("{}", error.message()),
+        None => eprintln!("Enroll stage {}/{}", stage, total),
+    };
+    eprintln!("Place your {} on the reader", finger);
+    let print = dev.enroll_sync(template, None, Some(progress), None)?;
+
+    let data = print.serialize()?;
+    let mut file = std::fs::File::create(output)?;
+    file.write_all(&data)?;
+
+    let mut report = describe_print(&print);
+    if let Json::Object(fields) = &mut report {
+        fields.push(("file", output.display().to_string().into()));
+    }
+    Ok((report, EXIT_OK))
+}
+
+fn verify(dev: &FpDevice, file: &Path) -> Outcome {
+    let print = read_print(file)?;
+    eprintln!("Place your finger on the reader");
+    let matched = dev.verify_sync(&print, None, None::<FpMatchCb<()>>, None, None)?;
+    let report = Json::object([
+        ("matched", matched.into()),
+        ("file", file.display().to_string().into()),
+    ]);
+    Ok((report, match_code(matched)))
+}
+
+fn identify(dev: &FpDevice, dir: &Path) -> Outcome {
+    let mut gallery: Gallery<PathBuf> = Gallery::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        match read_print(&path) {
+            Ok(print) => gallery.push(path, print),
+            Err(error) => eprintln!("Skipping {}: {}", path.display(), error.message),
+        }
+    }
+    if gallery.is_empty() {
+        return Err(format!("No prints found in {}", dir.display()).into());
+    }
+
+    eprintln!("Place your finger on the reader");
+    let res = dev.identify_in_sync(&gallery, None)?;
+    let report = Json::object([
+        ("matched", res.key.is_some().into()),
+        (
+            "file",
+            res.key
+                .as_ref()
+                .map(|file| file.display().to_string())
+                .into(),
+        ),
+        ("skipped", (res.skipped as i64).into()),
+    ]);
+    Ok((report, match_code(res.key.is_some())))
+}
+
+fn capture(dev: &FpDevice, output: &Path) -> Outcome {
+    eprintln!("Place your finger on the reader");
+    let image = dev.capture_sync(true, None)?;
+
+    let file = std::fs::File::create(output)?;
+    image.write_pgm(file)?;
+
+    let report = Json::object([
+        ("width", i64::from(image.width()).into()),
+        ("height", i64::from(image.height()).into()),
+        ("file", output.display().to_string().into()),
+    ]);
+    Ok((report, EXIT_OK))
+}
+
+fn list(dev: &FpDevice) -> Outcome {
+    let prints = dev.list_prints_sync(None)?;
+    Ok((
+        Json::Array(prints.iter().map(describe_print).collect()),
+        EXIT_OK,
+    ))
+}
+
+fn delete(
+    dev: &FpDevice,
+    file: Option<PathBuf>,
+    finger: Option<FpFinger>,
+    user: Option<String>,
+) -> Outcome {
+    let prints = match file {
+        Some(file) => vec![read_print(&file)?],
+        None => dev
+            .list_prints_sync(None)?
+            .into_iter()
+            .filter(|print| Some(print.finger()) == finger)
+            .filter(|print| user.is_none() || print.username() == user)
+            .collect(),
+    };
+    if prints.is_empty() {
+        return Err(FpError::DataNotFound("No matching print on the device".to_string()).into());
+    }
+
+    for print in &prints {
+        dev.delete_print_sync(print, None)?;
+    }
+    Ok((
+        Json::object([("deleted", (prints.len() as i64).into())]),
+        EXIT_OK,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let report = || Json::Null;
+        assert_eq!(exit_code(&Ok((report(), match_code(true)))), EXIT_OK);
+        assert_eq!(exit_code(&Ok((report(), match_code(false)))), EXIT_NO_MATCH);
+        let error: Outcome = Err(FpError::Busy("busy".to_string()).into());
+        assert_eq!(exit_code(&error), EXIT_ERROR);
+        let usage: Outcome = Err("Missing command".to_string().into());
+        assert_eq!(exit_code(&usage), EXIT_ERROR);
+    }
+}
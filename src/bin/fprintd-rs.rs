@@ -0,0 +1,81 @@
+//! fprintd compatible D-Bus service, see `fprintd-rs --help`.
+use std::{cell::Cell, path::PathBuf, process::ExitCode, rc::Rc};
+
+use libfprint_rs::{FileStore, FpContext, dbus::FprintService};
+
+const USAGE: &str = "\
+Usage: fprintd-rs [--session] [--state-dir <dir>]
+
+Exports the fprintd D-Bus interfaces for the fingerprint readers of this machine.
+
+Options:
+  --session               Use the session bus instead of the system bus
+  --state-dir <dir>       Store the prints in <dir> instead of /var/lib/fprint
+  -h, --help              Print this help";
+
+fn main() -> ExitCode {
+    let mut bus = gio::BusType::System;
+    let mut store = FileStore::system();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "--session" => bus = gio::BusType::Session,
+            "--state-dir" => match args.next() {
+                Some(dir) => store = FileStore::new(PathBuf::from(dir)),
+                None => {
+                    eprintln!("Missing value for {}\n\n{}", arg, USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ => {
+                eprintln!("Unknown option {}\n\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let connection = match gio::bus_get_sync(bus, gio::Cancellable::NONE) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("Failed to connect to the bus: {}", e.message());
+            return ExitCode::FAILURE;
+        }
+    };
+    let context = FpContext::new();
+    let _service = match FprintService::register(&connection, &context, store) {
+        Ok(service) => service,
+        Err(e) => {
+            eprintln!("Failed to export the service: {}", e.message());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let main_loop = glib::MainLoop::new(None, false);
+    let lost = Rc::new(Cell::new(false));
+    let owner = gio::bus_own_name_on_connection(
+        &connection,
+        FprintService::BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        |_, name| eprintln!("Acquired {}", name),
+        {
+            let (main_loop, lost) = (main_loop.clone(), lost.clone());
+            move |_, name| {
+                eprintln!("Lost or could not acquire {}", name);
+                lost.set(true);
+                main_loop.quit();
+            }
+        },
+    );
+    main_loop.run();
+    gio::bus_unown_name(owner);
+
+    if lost.get() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
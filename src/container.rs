@@ -0,0 +1,242 @@
+use std::fmt::Display;
+
+use glib::translate::IntoGlib;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{FpError, FpFinger, FpPrint};
+
+/// Versioned container for a serialized `FpPrint`.
+///
+/// It holds the opaque libfprint blob returned by `FpPrint::serialize`, alongside a copy of the print metadata that can
+/// be read without libfprint, i.e to index templates in a database. This is also the format used by the `Serialize`
+/// and `Deserialize` implementations of `FpPrint`.
+///
+/// In human readable formats such as JSON, the blob is encoded in base64.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpPrint, PrintContainer};
+///
+/// let container = PrintContainer::from_print(&print).unwrap();
+/// println!("{} enrolled on {}", container.metadata.finger, container.metadata.driver);
+/// let print = container.to_print().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintContainer {
+    /// Version of the container format, see `PrintContainer::VERSION`.
+    pub version: u32,
+    /// Metadata of the print, as it was when the container was created.
+    pub metadata: PrintMetadata,
+    /// The libfprint serialized print.
+    #[serde(with = "blob")]
+    pub data: Vec<u8>,
+}
+
+/// Metadata of a print stored in a `PrintContainer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrintMetadata {
+    /// See `FpPrint::driver`.
+    pub driver: String,
+    /// See `FpPrint::device_id`.
+    pub device_id: String,
+    /// See `FpPrint::finger`.
+    pub finger: FpFinger,
+    /// See `FpPrint::username`.
+    pub username: Option<String>,
+    /// See `FpPrint::description`.
+    pub description: Option<String>,
+    /// See `FpPrint::enroll_date`.
+    pub enroll_date: Option<EnrollDate>,
+    /// See `FpPrint::device_stored`.
+    pub device_stored: bool,
+}
+
+/// Calendar date of an enrollment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EnrollDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Display for EnrollDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl PrintContainer {
+    /// Current version of the container format.
+    pub const VERSION: u32 = 1;
+
+    /// Serializes `print` into a new container.
+    pub fn from_print(print: &FpPrint) -> Result<Self, FpError> {
+        let enroll_date = print
+            .enroll_date()
+            .filter(glib::Date::valid)
+            .map(|date| EnrollDate {
+                year: date.year(),
+                month: date.month().into_glib() as u8,
+                day: date.day(),
+            });
+        let metadata = PrintMetadata {
+            driver: print.driver(),
+            device_id: print.device_id(),
+            finger: print.finger(),
+            username: print.username(),
+            description: print.description(),
+            enroll_date,
+            device_stored: print.device_stored(),
+        };
+
+        Ok(Self {
+            version: Self::VERSION,
+            metadata,
+            data: print.serialize()?,
+        })
+    }
+
+    /// Deserializes the print of the container. The metadata of the print is the one of the blob, the `metadata`
+    /// field is not used.
+    pub fn to_print(&self) -> Result<FpPrint, FpError> {
+        if self.version == 0 || self.version > Self::VERSION {
+            return Err(FpError::DataInvalid(format!(
+                "Unsupported print container version {}",
+                self.version
+            )));
+        }
+        Ok(FpPrint::deserialize(&self.data)?)
+    }
+}
+
+impl Serialize for FpPrint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PrintContainer::from_print(self)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FpPrint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PrintContainer::deserialize(deserializer)?
+            .to_print()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Encodes the blob in base64 for human readable formats, and as bytes otherwise.
+mod blob {
+    use serde::{
+        Deserialize, Deserializer, Serializer,
+        de::{Error, SeqAccess, Visitor},
+    };
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode(data))
+        } else {
+            serializer.serialize_bytes(data)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            decode(&encoded).ok_or_else(|| D::Error::custom("Invalid base64 print data"))
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    /// Accepts the blob either as bytes or as a sequence, depending on what the format supports.
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "print data bytes")
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                data.push(byte);
+            }
+            Ok(data)
+        }
+    }
+
+    pub(super) fn encode(data: &[u8]) -> String {
+        let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+        for chunk in data.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    pub(super) fn decode(encoded: &str) -> Option<Vec<u8>> {
+        let encoded = encoded.as_bytes();
+        if !encoded.len().is_multiple_of(4) {
+            return None;
+        }
+        let mut data = Vec::with_capacity(encoded.len() / 4 * 3);
+        for chunk in encoded.chunks(4) {
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+            if padding > 2 {
+                return None;
+            }
+            let mut group = 0u32;
+            for &c in &chunk[..4 - padding] {
+                let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+                group = group << 6 | value;
+            }
+            group <<= 6 * padding as u32;
+            data.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+        }
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::blob::{decode, encode};
+
+    #[test]
+    fn base64_roundtrip() {
+        for (data, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode(data), encoded);
+            assert_eq!(decode(encoded).unwrap(), data);
+        }
+        assert!(decode("Zm9").is_none());
+        assert!(decode("Zm9*").is_none());
+    }
+}
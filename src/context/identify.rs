@@ -0,0 +1,193 @@
+use std::{cell::RefCell, rc::Rc};
+
+use futures_channel::oneshot;
+use gio::{
+    Cancellable, CancelledHandlerId,
+    prelude::{CancellableExt, CancellableExtManual},
+};
+
+use crate::{FpDevice, FpDeviceFeature, FpError, FpFuture, FpMatchCb, FpPrint};
+
+use super::FpContext;
+
+/// Result of `FpContext::identify_any`: the device the finger was scanned on, the matching print if any and the
+/// scanned print if the driver provides one.
+pub type IdentifyAnyResult = (FpDevice, Option<FpPrint>, Option<FpPrint>);
+
+/// Identification running on several devices, resolved by the first one to finish.
+struct Race<P> {
+    callback: Option<P>,
+    /// Number of devices still running.
+    pending: usize,
+    /// Error returned if no device succeeds: the first retry error, or else the first error. Retry errors only end
+    /// the identification of a device once the race is cancelled.
+    error: Option<FpError>,
+    cancellables: Vec<Cancellable>,
+    parent: Cancellable,
+    parent_handler: Option<CancelledHandlerId>,
+}
+
+impl FpContext {
+    /// Starts `identify` on every open device supporting it, with the prints of `prints` that are compatible with
+    /// it. The first device reporting a result wins and the other devices are cancelled. A device reporting a retry
+    /// error is restarted.
+    fn identify_any_async<P>(&self, prints: &[FpPrint], cancellable: &Cancellable, callback: P)
+    where
+        P: FnOnce(Result<IdentifyAnyResult, FpError>) + 'static,
+    {
+        let devices: Vec<(FpDevice, Vec<FpPrint>)> = self
+            .devices()
+            .into_iter()
+            .filter(|dev| dev.is_open() && dev.has_feature(FpDeviceFeature::IDENTIFY))
+            .filter_map(|dev| {
+                let compatible: Vec<FpPrint> = prints
+                    .iter()
+                    .filter(|print| print.compatible(&dev))
+                    .cloned()
+                    .collect();
+                (!compatible.is_empty()).then_some((dev, compatible))
+            })
+            .collect();
+        if devices.is_empty() {
+            return callback(Err(FpError::DataNotFound(
+                "No open device can identify the given prints".to_string(),
+            )));
+        }
+
+        let race = Rc::new(RefCell::new(Race {
+            callback: Some(callback),
+            pending: devices.len(),
+            error: None,
+            cancellables: Vec::new(),
+            parent: cancellable.clone(),
+            parent_handler: None,
+        }));
+        for (dev, prints) in devices {
+            let child = Cancellable::new();
+            race.borrow_mut().cancellables.push(child.clone());
+            identify_on(race.clone(), dev, prints, child);
+        }
+
+        let weak = Rc::downgrade(&race);
+        let handler = cancellable.connect_cancelled_local(move |_| {
+            if let Some(race) = weak.upgrade() {
+                let cancellables = race.borrow().cancellables.clone();
+                cancellables.iter().for_each(CancellableExt::cancel);
+            }
+        });
+        race.borrow_mut().parent_handler = handler;
+    }
+
+    #[cfg(not(doctest))]
+    /// Identify a finger on whichever open device it is scanned on.
+    ///
+    /// Identification runs concurrently on every open device supporting `FpDeviceFeature::IDENTIFY`, against the
+    /// prints of `prints` that are compatible with it. The first device to report a result, match or not, wins and
+    /// the operation is cancelled on the other devices. A device reporting a retry error, such as a swipe too short,
+    /// starts identifying again, so the user can retry on the same reader or on another one. Other errors do not
+    /// stop the other devices either: they are only returned once every device failed. Retry errors are only
+    /// returned if the operation is cancelled. Returns `FpError::DataNotFound` if no open device can use the prints.
+    ///
+    /// Dropping the future cancels the operation on every device.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     for dev in &devices {
+    ///         dev.open().await.unwrap();
+    ///     }
+    ///     let (dev, matched, _scanned) = ctx.identify_any(&enrolled_prints).await.unwrap();
+    ///     println!("{} matched: {}", dev.name(), matched.is_some());
+    /// });
+    /// ```
+    pub fn identify_any(&self, prints: &[FpPrint]) -> FpFuture<IdentifyAnyResult> {
+        let prints = prints.to_vec();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.identify_any_async(&prints, cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Identify a finger on whichever open device it is scanned on, blocking while iterating the thread default
+    /// `glib::MainContext`.
+    ///
+    /// See `FpContext::identify_any`.
+    pub fn identify_any_sync(
+        &self,
+        prints: &[FpPrint],
+        cancellable: Option<&Cancellable>,
+    ) -> Result<IdentifyAnyResult, FpError> {
+        let cancellable = cancellable.cloned().unwrap_or_default();
+        let (sender, receiver) = oneshot::channel();
+        self.identify_any_async(prints, &cancellable, move |res| {
+            let _ = sender.send(res);
+        });
+        glib::MainContext::ref_thread_default()
+            .block_on(receiver)
+            .expect("identify_any callback dropped without a result")
+    }
+}
+
+/// Runs `identify` on `dev` for the race, again after each retry error until `cancellable` is cancelled.
+fn identify_on<P>(
+    race: Rc<RefCell<Race<P>>>,
+    dev: FpDevice,
+    prints: Vec<FpPrint>,
+    cancellable: Cancellable,
+) where
+    P: FnOnce(Result<IdentifyAnyResult, FpError>) + 'static,
+{
+    let device = dev.clone();
+    dev.identify_async(
+        &prints.clone(),
+        &cancellable.clone(),
+        None::<FpMatchCb<()>>,
+        None,
+        move |res| match res {
+            Err(e) if e.is_retry() && !cancellable.is_cancelled() => {
+                identify_on(race, device, prints, cancellable)
+            }
+            res => finish(
+                &race,
+                res.map(|(matched, scanned)| (device, matched, scanned)),
+            ),
+        },
+    );
+}
+
+fn finish<P>(race: &Rc<RefCell<Race<P>>>, res: Result<IdentifyAnyResult, FpError>)
+where
+    P: FnOnce(Result<IdentifyAnyResult, FpError>),
+{
+    let mut state = race.borrow_mut();
+    state.pending -= 1;
+    let res = match res {
+        Err(e) => {
+            let error = match state.error.take() {
+                Some(first) if first.is_retry() || !e.is_retry() => first,
+                _ => e,
+            };
+            if state.pending > 0 {
+                state.error = Some(error);
+                return;
+            }
+            Err(error)
+        }
+        res => res,
+    };
+    let Some(callback) = state.callback.take() else {
+        return;
+    };
+    let cancellables = std::mem::take(&mut state.cancellables);
+    if let Some(handler) = state.parent_handler.take() {
+        state.parent.disconnect_cancelled(handler);
+    }
+    drop(state);
+
+    // Cancelling the device that just finished is harmless
+    cancellables.iter().for_each(CancellableExt::cancel);
+    callback(res);
+}
@@ -0,0 +1,188 @@
+mod identify;
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+use futures_core::Stream;
+use glib::{SignalHandlerId, object::ObjectExt, translate::ToGlibPtr, wrapper};
+
+use crate::FpDevice;
+
+pub use identify::IdentifyAnyResult;
+
+wrapper! {
+    /// This struct allows you to discover fingerprint scanning hardware. This is the starting point when integrating libfprint-rs into your software.
+    pub struct FpContext(Object<libfprint_sys::FpContext, libfprint_sys::FpContextClass>);
+    match fn {
+        type_ => || libfprint_sys::fp_context_get_type() as usize,
+    }
+}
+impl FpContext {
+    #[cfg(not(doctest))]
+    /// Create a new `FpContext`
+    /// # Examples:
+    /// ```rust
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let context = FpContext::new();
+    /// ```
+    pub fn new() -> Self {
+        unsafe { glib::translate::from_glib_none(libfprint_sys::fp_context_new()) }
+    }
+    #[cfg(not(doctest))]
+    /// Get the list of devices connected to the system
+    /// # Examples:
+    /// ```rust
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let context = FpContext::new();
+    /// let devices = context.devices();
+    /// ```
+    pub fn devices(&self) -> Vec<FpDevice> {
+        use glib::translate::FromGlibPtrContainer;
+
+        unsafe {
+            let devs = libfprint_sys::fp_context_get_devices(self.to_glib_none().0);
+
+            let devs = devs.cast::<glib::ffi::GPtrArray>();
+            FromGlibPtrContainer::from_glib_none(devs)
+        }
+    }
+
+    /// Enumerate all the devices connected to the system
+    ///
+    /// This function will enumerate all the devices connected to the system and add them to the context.
+    pub fn enumerate(&self) {
+        unsafe {
+            libfprint_sys::fp_context_enumerate(self.to_glib_none().0);
+        }
+    }
+}
+
+impl FpContext {
+    /// Connect to the `device-added` signal, emitted when a new device is plugged in.
+    ///
+    /// Hotplug events are only reported after `FpContext::enumerate` (or `FpContext::devices`) was called, and while the
+    /// thread default `glib::MainContext` is being iterated.
+    pub fn connect_device_added<F: Fn(&FpContext, &FpDevice) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_device_signal("device-added", f)
+    }
+    /// Connect to the `device-removed` signal, emitted when a device is unplugged.
+    ///
+    /// The removed device can no longer be used, any operation in progress on it will fail with
+    /// `FpError::Removed`.
+    pub fn connect_device_removed<F: Fn(&FpContext, &FpDevice) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_device_signal("device-removed", f)
+    }
+
+    fn connect_device_signal<F: Fn(&FpContext, &FpDevice) + 'static>(
+        &self,
+        signal: &str,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_local(signal, false, move |values| {
+            let ctx = values[0]
+                .get::<FpContext>()
+                .expect("signal emitted with an invalid context");
+            let device = values[1]
+                .get::<FpDevice>()
+                .expect("signal emitted with an invalid device");
+            f(&ctx, &device);
+            None
+        })
+    }
+
+    #[cfg(not(doctest))]
+    /// Returns a stream of the devices added to or removed from the system.
+    ///
+    /// The stream is driven by the thread default `glib::MainContext`, the signal handlers are disconnected when it is
+    /// dropped.
+    /// # Examples:
+    /// ```rust
+    /// use futures_util::StreamExt;
+    /// use libfprint_rs::{ContextEvent, FpContext};
+    ///
+    /// let context = FpContext::new();
+    /// context.enumerate();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     let mut events = context.events();
+    ///     while let Some(event) = events.next().await {
+    ///         match event {
+    ///             ContextEvent::Added(dev) => println!("Plugged in: {}", dev.name()),
+    ///             ContextEvent::Removed(dev) => println!("Unplugged: {}", dev.name()),
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn events(&self) -> ContextEvents {
+        let (sender, receiver) = unbounded();
+
+        let added_sender = sender.clone();
+        let added = self.connect_device_added(move |_, device| {
+            send_event(&added_sender, ContextEvent::Added(device.clone()));
+        });
+        let removed = self.connect_device_removed(move |_, device| {
+            send_event(&sender, ContextEvent::Removed(device.clone()));
+        });
+
+        ContextEvents {
+            context: self.clone(),
+            receiver,
+            handlers: Some((added, removed)),
+        }
+    }
+}
+
+/// Hotplug event reported by `FpContext::events`.
+#[derive(Debug, Clone)]
+pub enum ContextEvent {
+    /// A device was plugged in.
+    Added(FpDevice),
+    /// A device was unplugged.
+    Removed(FpDevice),
+}
+
+/// Stream of `ContextEvent`s, created by `FpContext::events`.
+pub struct ContextEvents {
+    context: FpContext,
+    receiver: UnboundedReceiver<ContextEvent>,
+    handlers: Option<(SignalHandlerId, SignalHandlerId)>,
+}
+
+impl Stream for ContextEvents {
+    type Item = ContextEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl Drop for ContextEvents {
+    fn drop(&mut self) {
+        if let Some((added, removed)) = self.handlers.take() {
+            self.context.disconnect(added);
+            self.context.disconnect(removed);
+        }
+    }
+}
+
+fn send_event(sender: &UnboundedSender<ContextEvent>, event: ContextEvent) {
+    // The stream might be gone already, in which case nobody is interested in the event
+    let _ = sender.unbounded_send(event);
+}
+
+impl Default for FpContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
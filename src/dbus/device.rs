@@ -0,0 +1,701 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gio::{
+    BusNameWatcherFlags, Cancellable, DBusConnection, DBusInterfaceInfo, DBusMethodInvocation,
+    RegistrationId, prelude::*,
+};
+use glib::{SignalHandlerId, Variant, VariantDict};
+
+use crate::{
+    EnrollTemplate, FpDevice, FpDeviceFeature, FpError, FpFinger, FpFingerStatus, FpMatchCb,
+    FpPrint, FpScanType, PrintStore,
+};
+
+use super::{
+    DEVICE_INTERFACE, ServiceError, enroll_status, finger_name, parse_finger, resolve_user,
+    verify_status,
+};
+
+/// Error of a request, replied to the caller.
+type RequestError = (ServiceError, String);
+
+/// Callbacks run once an action is over.
+type Callbacks = Vec<Box<dyn FnOnce()>>;
+
+/// A `net.reactivated.Fprint.Device` object.
+pub(super) struct Device {
+    dev: FpDevice,
+    path: String,
+    connection: DBusConnection,
+    store: Rc<dyn PrintStore>,
+    state: RefCell<State>,
+    finger_handler: RefCell<Option<SignalHandlerId>>,
+}
+
+#[derive(Default)]
+struct State {
+    claim: Option<Claim>,
+    action: Option<Action>,
+    /// Set while the device is closed by a release, or opened for a deletion without claim. Holds the requests that
+    /// need the device, run again once it settled.
+    settling: Option<Callbacks>,
+}
+
+struct Claim {
+    sender: String,
+    /// `None` until the device is open.
+    username: Option<String>,
+    /// Stops watching the claiming client, which releases the device when it leaves the bus.
+    unwatch: Option<Box<dyn FnOnce()>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActionKind {
+    Enroll,
+    Verify,
+}
+
+/// An enrollment or verification. As with fprintd, it lasts until the matching stop method is called, even once the
+/// operation is over.
+struct Action {
+    kind: ActionKind,
+    cancellable: Cancellable,
+    /// Whether the operation is over and its final status was emitted.
+    done: bool,
+    /// Called once the operation is over, i.e to reply to `EnrollStop`.
+    on_done: Callbacks,
+}
+
+impl Device {
+    pub(super) fn new(
+        dev: FpDevice,
+        path: String,
+        connection: &DBusConnection,
+        store: Rc<dyn PrintStore>,
+    ) -> Rc<Self> {
+        let (conn, object_path) = (connection.clone(), path.clone());
+        let handler = dev.connect_finger_status_notify(move |dev| {
+            let status = dev.finger_status();
+            let changed = VariantDict::new(None);
+            changed.insert_value(
+                "finger-present",
+                &status.contains(FpFingerStatus::PRESENT).to_variant(),
+            );
+            changed.insert_value(
+                "finger-needed",
+                &status.contains(FpFingerStatus::NEEDED).to_variant(),
+            );
+            let args = Variant::tuple_from_iter([
+                DEVICE_INTERFACE.to_variant(),
+                changed.end(),
+                Vec::<String>::new().to_variant(),
+            ]);
+            let _ = conn.emit_signal(
+                None,
+                &object_path,
+                "org.freedesktop.DBus.Properties",
+                "PropertiesChanged",
+                Some(&args),
+            );
+        });
+
+        Rc::new(Self {
+            dev,
+            path,
+            connection: connection.clone(),
+            store,
+            state: RefCell::new(State::default()),
+            finger_handler: RefCell::new(Some(handler)),
+        })
+    }
+
+    pub(super) fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(super) fn register(
+        self: &Rc<Self>,
+        connection: &DBusConnection,
+        interface: &DBusInterfaceInfo,
+    ) -> Result<RegistrationId, glib::Error> {
+        let this = self.clone();
+        let props = self.clone();
+        connection
+            .register_object(&self.path, interface)
+            .method_call(move |_, sender, _, _, method, params, invocation| {
+                this.method_call(sender.unwrap_or_default(), method, params, invocation)
+            })
+            .property(move |_, _, _, _, prop| props.property(prop))
+            .build()
+    }
+
+    /// Releases the device and stops listening to it, once the object is unregistered.
+    pub(super) fn shutdown(self: &Rc<Self>) {
+        if let Some(handler) = self.finger_handler.take() {
+            self.dev.disconnect(handler);
+        }
+        if self.state.borrow().claim.is_some() {
+            self.release(None);
+        }
+    }
+
+    fn method_call(
+        self: &Rc<Self>,
+        sender: &str,
+        method: &str,
+        params: Variant,
+        invocation: DBusMethodInvocation,
+    ) {
+        // GDBus checks the arguments against the introspection data before calling us
+        let arg = || params.get::<(String,)>().unwrap().0;
+        let sender = sender.to_string();
+        match method {
+            "Claim" => self.claim(sender, arg(), invocation),
+            "Release" => match self.claimed_user(&sender) {
+                Ok(_) => self.release(Some(invocation)),
+                Err((error, message)) => error.reply(invocation, &message),
+            },
+            "EnrollStart" => self.enroll_start(&sender, &arg(), invocation),
+            "EnrollStop" => self.stop(&sender, ActionKind::Enroll, invocation),
+            "VerifyStart" => self.verify_start(&sender, &arg(), invocation),
+            "VerifyStop" => self.stop(&sender, ActionKind::Verify, invocation),
+            "ListEnrolledFingers" => self.list_fingers(sender, arg(), invocation),
+            "DeleteEnrolledFingers" => self.delete_user(sender, arg(), invocation),
+            "DeleteEnrolledFingers2" => self.delete_claimed(&sender, None, invocation),
+            "DeleteEnrolledFinger" => self.delete_claimed(&sender, Some(&arg()), invocation),
+            _ => unreachable!("Method {} is not in the introspection data", method),
+        }
+    }
+
+    fn property(&self, prop: &str) -> Variant {
+        match prop {
+            "name" => self.dev.name().to_variant(),
+            "num-enroll-stages" => self.dev.nr_enroll_stage().to_variant(),
+            "scan-type" => match self.dev.scan_type() {
+                FpScanType::Swipe => "swipe",
+                FpScanType::Press => "press",
+            }
+            .to_variant(),
+            "finger-present" => self
+                .dev
+                .finger_status()
+                .contains(FpFingerStatus::PRESENT)
+                .to_variant(),
+            "finger-needed" => self
+                .dev
+                .finger_status()
+                .contains(FpFingerStatus::NEEDED)
+                .to_variant(),
+            _ => unreachable!("Property {} is not in the introspection data", prop),
+        }
+    }
+
+    fn emit(&self, signal: &str, args: Variant) {
+        emit(&self.connection, &self.path, signal, args);
+    }
+
+    /// User of the claim of `sender`.
+    fn claimed_user(&self, sender: &str) -> Result<String, RequestError> {
+        match &self.state.borrow().claim {
+            Some(claim) if claim.sender != sender => Err((
+                ServiceError::AlreadyInUse,
+                "Device was claimed by another client".to_string(),
+            )),
+            Some(Claim {
+                username: Some(username),
+                ..
+            }) => Ok(username.clone()),
+            _ => Err((
+                ServiceError::ClaimDevice,
+                "Device was not claimed before use".to_string(),
+            )),
+        }
+    }
+
+    /// Starts an action of `kind` if the device is idle.
+    fn begin(&self, kind: ActionKind) -> Result<Cancellable, RequestError> {
+        let mut state = self.state.borrow_mut();
+        if state.action.is_some() {
+            return Err((
+                ServiceError::AlreadyInUse,
+                "Another operation is already in progress".to_string(),
+            ));
+        }
+        let cancellable = Cancellable::new();
+        state.action = Some(Action {
+            kind,
+            cancellable: cancellable.clone(),
+            done: false,
+            on_done: Vec::new(),
+        });
+        Ok(cancellable)
+    }
+
+    /// Runs `request` again once the device settled, see `State::settling`.
+    fn retry_when_settled(&self, request: impl FnOnce() + 'static) {
+        if let Some(waiting) = self.state.borrow_mut().settling.as_mut() {
+            waiting.push(Box::new(request));
+        }
+    }
+
+    /// Marks the device as settled, running the requests that waited for it.
+    fn settled(&self) {
+        let waiting = self.state.borrow_mut().settling.take().unwrap_or_default();
+        waiting.into_iter().for_each(|f| f());
+    }
+
+    fn claim(self: &Rc<Self>, sender: String, username: String, invocation: DBusMethodInvocation) {
+        if self.state.borrow().claim.is_some() {
+            return ServiceError::AlreadyInUse.reply(invocation, "Device was already claimed");
+        }
+        if self.state.borrow().settling.is_some() {
+            let this = self.clone();
+            self.retry_when_settled(move || this.claim(sender, username, invocation));
+            return;
+        }
+        self.state.borrow_mut().claim = Some(Claim {
+            sender: sender.clone(),
+            username: None,
+            unwatch: None,
+        });
+
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            let res = match resolve_user(&this.connection, &sender, &username).await {
+                Ok(user) => this
+                    .dev
+                    .open()
+                    .await
+                    .map(|()| user)
+                    .map_err(|e| (ServiceError::Internal, e.to_string())),
+                Err(e) => Err(e),
+            };
+            let user = match res {
+                Ok(user) => user,
+                Err((error, message)) => {
+                    this.state.borrow_mut().claim = None;
+                    return error.reply(invocation, &message);
+                }
+            };
+
+            let weak = Rc::downgrade(&this);
+            let watcher = gio::bus_watch_name_on_connection(
+                &this.connection,
+                &sender,
+                BusNameWatcherFlags::NONE,
+                |_, _, _| {},
+                move |_, _| {
+                    if let Some(this) = weak.upgrade() {
+                        this.release(None);
+                    }
+                },
+            );
+            if let Some(claim) = this.state.borrow_mut().claim.as_mut() {
+                claim.username = Some(user);
+                claim.unwatch = Some(Box::new(move || gio::bus_unwatch_name(watcher)));
+            }
+            invocation.return_value(None);
+        });
+    }
+
+    /// Drops the claim and closes the device, once the running action, if any, is cancelled. A new claim waits for
+    /// the device to be closed.
+    fn release(self: &Rc<Self>, invocation: Option<DBusMethodInvocation>) {
+        let mut state = self.state.borrow_mut();
+        let Some(claim) = state.claim.take() else {
+            return;
+        };
+        state.settling.get_or_insert_with(Vec::new);
+        if let Some(unwatch) = claim.unwatch {
+            unwatch();
+        }
+
+        let this = self.clone();
+        let close = move || {
+            glib::MainContext::default().spawn_local(async move {
+                let res = this.dev.close().await;
+                this.settled();
+                match (invocation, res) {
+                    (Some(invocation), Ok(())) => invocation.return_value(None),
+                    (Some(invocation), Err(e)) => {
+                        ServiceError::Internal.reply(invocation, &e.to_string())
+                    }
+                    (None, _) => {}
+                }
+            });
+        };
+        match state.action.as_mut() {
+            Some(action) if !action.done => {
+                action.on_done.push(Box::new(close));
+                let cancellable = action.cancellable.clone();
+                drop(state);
+                cancellable.cancel();
+            }
+            _ => {
+                state.action = None;
+                drop(state);
+                close();
+            }
+        }
+    }
+
+    /// Ends the action of `kind`, cancelling it and replying once it is over if it is still running.
+    fn stop(&self, sender: &str, kind: ActionKind, invocation: DBusMethodInvocation) {
+        if let Err((error, message)) = self.claimed_user(sender) {
+            return error.reply(invocation, &message);
+        }
+        let mut state = self.state.borrow_mut();
+        match state.action.as_mut() {
+            Some(action) if action.kind == kind && action.done => {
+                state.action = None;
+                drop(state);
+                invocation.return_value(None);
+            }
+            Some(action) if action.kind == kind => {
+                action
+                    .on_done
+                    .push(Box::new(move || invocation.return_value(None)));
+                let cancellable = action.cancellable.clone();
+                drop(state);
+                cancellable.cancel();
+            }
+            _ => ServiceError::NoActionInProgress.reply(invocation, "No operation in progress"),
+        }
+    }
+
+    /// Marks the action as over, returning whether it was stopped, and the callbacks to run once its final status is
+    /// emitted. A stopped action is dropped right away.
+    fn complete_action(&self) -> Option<(bool, Callbacks)> {
+        let mut state = self.state.borrow_mut();
+        let action = state.action.as_mut()?;
+        let stopped = action.cancellable.is_cancelled();
+        let on_done = std::mem::take(&mut action.on_done);
+        if stopped {
+            state.action = None;
+        } else {
+            action.done = true;
+        }
+        Some((stopped, on_done))
+    }
+
+    fn enroll_start(self: &Rc<Self>, sender: &str, finger: &str, invocation: DBusMethodInvocation) {
+        let Some(finger) = parse_finger(finger) else {
+            return ServiceError::InvalidFingername
+                .reply(invocation, &format!("Invalid finger name {}", finger));
+        };
+        let res = self.claimed_user(sender).and_then(|username| {
+            let template = EnrollTemplate::builder(&self.dev)
+                .finger(finger)
+                .username(&username)
+                .build()
+                .map_err(|e| (ServiceError::Internal, e.to_string()))?;
+            Ok((username, template, self.begin(ActionKind::Enroll)?))
+        });
+        let (username, template, cancellable) = match res {
+            Ok(res) => res,
+            Err((error, message)) => return error.reply(invocation, &message),
+        };
+
+        let (connection, path) = (self.connection.clone(), self.path.clone());
+        let progress = move |_: &FpDevice,
+                             _: i32,
+                             _: Option<FpPrint>,
+                             error: Option<glib::Error>,
+                             _: &Option<()>| {
+            let result = match error {
+                None => "enroll-stage-passed",
+                Some(error) => enroll_status(&error.into()),
+            };
+            emit(
+                &connection,
+                &path,
+                "EnrollStatus",
+                (result, false).to_variant(),
+            );
+        };
+        let this = self.clone();
+        self.dev.enroll_async(
+            template.into_print(),
+            &cancellable,
+            Some(progress),
+            None,
+            move |res| {
+                let Some((stopped, on_done)) = this.complete_action() else {
+                    return;
+                };
+                let result = match res {
+                    Ok(print) => match this.store.save(&username, &print) {
+                        Ok(()) => Some("enroll-completed"),
+                        Err(e) => {
+                            // The client only gets the status, tell the admin why
+                            eprintln!("Failed to save the print of {}: {}", username, e);
+                            Some("enroll-failed")
+                        }
+                    },
+                    Err(_) if stopped => None,
+                    Err(e) => Some(enroll_status(&e)),
+                };
+                if let Some(result) = result {
+                    this.emit("EnrollStatus", (result, true).to_variant());
+                }
+                on_done.into_iter().for_each(|f| f());
+            },
+        );
+        invocation.return_value(None);
+    }
+
+    fn verify_start(self: &Rc<Self>, sender: &str, finger: &str, invocation: DBusMethodInvocation) {
+        let username = match self.claimed_user(sender) {
+            Ok(username) => username,
+            Err((error, message)) => return error.reply(invocation, &message),
+        };
+        let prints = if finger == "any" || finger.is_empty() {
+            self.store.load_user(&self.dev, &username)
+        } else {
+            let Some(finger) = parse_finger(finger) else {
+                return ServiceError::InvalidFingername
+                    .reply(invocation, &format!("Invalid finger name {}", finger));
+            };
+            self.store
+                .load(&self.dev, &username, finger)
+                .map(|print| print.into_iter().collect())
+        };
+        let mut prints = match prints {
+            Ok(prints) if prints.is_empty() => {
+                return ServiceError::NoEnrolledPrints
+                    .reply(invocation, "No fingerprints enrolled");
+            }
+            Ok(prints) => prints,
+            Err(e) => return ServiceError::Internal.reply(invocation, &e.to_string()),
+        };
+        let cancellable = match self.begin(ActionKind::Verify) {
+            Ok(cancellable) => cancellable,
+            Err((error, message)) => return error.reply(invocation, &message),
+        };
+
+        // Without identification support, fall back to the first enrolled finger like fprintd
+        if !self.dev.has_feature(FpDeviceFeature::IDENTIFY) {
+            prints.truncate(1);
+        }
+        let selected = match prints.as_slice() {
+            [print] => finger_name(print.finger()).unwrap_or("any"),
+            _ => "any",
+        };
+        invocation.return_value(None);
+        self.emit("VerifyFingerSelected", (selected,).to_variant());
+        self.run_verify(prints, cancellable);
+    }
+
+    /// Verifies or identifies against `prints`, restarting after each retry until the action is over.
+    fn run_verify(self: &Rc<Self>, prints: Vec<FpPrint>, cancellable: Cancellable) {
+        let this = self.clone();
+        let (scan, canc) = (prints.clone(), cancellable.clone());
+        let done = move |res: Result<bool, FpError>| this.verify_done(res, scan, canc);
+        if prints.len() > 1 {
+            self.dev.identify_async(
+                &prints,
+                &cancellable,
+                None::<FpMatchCb<()>>,
+                None,
+                move |res| done(res.map(|(matched, _)| matched.is_some())),
+            );
+        } else {
+            self.dev.verify_async(
+                &prints[0],
+                &cancellable,
+                None::<FpMatchCb<()>>,
+                None,
+                move |res| done(res.map(|(matched, _)| matched)),
+            );
+        }
+    }
+
+    fn verify_done(
+        self: &Rc<Self>,
+        res: Result<bool, FpError>,
+        prints: Vec<FpPrint>,
+        cancellable: Cancellable,
+    ) {
+        if let Err(e) = &res
+            && e.is_retry()
+            && !cancellable.is_cancelled()
+        {
+            self.emit("VerifyStatus", (verify_status(e), false).to_variant());
+            return self.run_verify(prints, cancellable);
+        }
+
+        let Some((stopped, on_done)) = self.complete_action() else {
+            return;
+        };
+        let result = match res {
+            Ok(true) => Some("verify-match"),
+            Ok(false) => Some("verify-no-match"),
+            Err(_) if stopped => None,
+            Err(e) => Some(verify_status(&e)),
+        };
+        if let Some(result) = result {
+            self.emit("VerifyStatus", (result, true).to_variant());
+        }
+        on_done.into_iter().for_each(|f| f());
+    }
+
+    fn list_fingers(
+        self: &Rc<Self>,
+        sender: String,
+        username: String,
+        invocation: DBusMethodInvocation,
+    ) {
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            let res = match resolve_user(&this.connection, &sender, &username).await {
+                Ok(user) => this
+                    .store
+                    .fingers(&this.dev, &user)
+                    .map_err(|e| (ServiceError::Internal, e.to_string())),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(fingers) if fingers.is_empty() => {
+                    ServiceError::NoEnrolledPrints.reply(invocation, "No fingerprints enrolled")
+                }
+                Ok(fingers) => {
+                    let names: Vec<&str> = fingers.into_iter().filter_map(finger_name).collect();
+                    invocation.return_value(Some(&(names,).to_variant()));
+                }
+                Err((error, message)) => error.reply(invocation, &message),
+            }
+        });
+    }
+
+    /// `DeleteEnrolledFingers`, which does not require a claim. Without one, a device with a storage is opened for
+    /// the deletion, as fprintd does, so that the prints are deleted from it as well.
+    fn delete_user(
+        self: &Rc<Self>,
+        sender: String,
+        username: String,
+        invocation: DBusMethodInvocation,
+    ) {
+        let mut state = self.state.borrow_mut();
+        match &state.claim {
+            Some(claim) if claim.sender != sender => {
+                return ServiceError::AlreadyInUse
+                    .reply(invocation, "Device was claimed by another client");
+            }
+            Some(Claim { username: None, .. }) => {
+                return ServiceError::AlreadyInUse.reply(invocation, "Device is being claimed");
+            }
+            Some(_) if state.action.is_some() => {
+                return ServiceError::AlreadyInUse
+                    .reply(invocation, "Another operation is already in progress");
+            }
+            _ => {}
+        }
+        let open = state.claim.is_none() && self.dev.has_feature(FpDeviceFeature::STORAGE_DELETE);
+        if open {
+            if state.settling.is_some() {
+                drop(state);
+                let this = self.clone();
+                self.retry_when_settled(move || this.delete_user(sender, username, invocation));
+                return;
+            }
+            state.settling = Some(Vec::new());
+        }
+        drop(state);
+
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            let res = match resolve_user(&this.connection, &sender, &username).await {
+                Ok(user) if open => match this.dev.open().await {
+                    Ok(()) => {
+                        let res = this.delete_prints(&user, None).await;
+                        let _ = this.dev.close().await;
+                        res
+                    }
+                    Err(e) => Err((ServiceError::Internal, e.to_string())),
+                },
+                Ok(user) => this.delete_prints(&user, None).await,
+                Err(e) => Err(e),
+            };
+            if open {
+                this.settled();
+            }
+            match res {
+                Ok(()) => invocation.return_value(None),
+                Err((error, message)) => error.reply(invocation, &message),
+            }
+        });
+    }
+
+    /// `DeleteEnrolledFingers2` and `DeleteEnrolledFinger`, acting on the user of the claim.
+    fn delete_claimed(
+        self: &Rc<Self>,
+        sender: &str,
+        finger: Option<&str>,
+        invocation: DBusMethodInvocation,
+    ) {
+        let finger = match finger.map(|name| (name, parse_finger(name))) {
+            Some((name, None)) => {
+                return ServiceError::InvalidFingername
+                    .reply(invocation, &format!("Invalid finger name {}", name));
+            }
+            Some((_, finger)) => finger,
+            None => None,
+        };
+        let username = match self.claimed_user(sender) {
+            Ok(username) => username,
+            Err((error, message)) => return error.reply(invocation, &message),
+        };
+        if self.state.borrow().action.is_some() {
+            return ServiceError::AlreadyInUse
+                .reply(invocation, "Another operation is already in progress");
+        }
+
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            match this.delete_prints(&username, finger).await {
+                Ok(()) => invocation.return_value(None),
+                Err((error, message)) => error.reply(invocation, &message),
+            }
+        });
+    }
+
+    /// Deletes the prints of `finger`, or of all the fingers, of `user` from the store, and from the device storage
+    /// if it has one, in which case the device must be open.
+    async fn delete_prints(
+        &self,
+        user: &str,
+        finger: Option<FpFinger>,
+    ) -> Result<(), RequestError> {
+        let store_error = |e: crate::StoreError| (ServiceError::PrintsNotDeleted, e.to_string());
+        let enrolled = self.store.fingers(&self.dev, user).map_err(store_error)?;
+        let fingers = match finger {
+            Some(finger) if !enrolled.contains(&finger) => {
+                return Err((
+                    ServiceError::NoEnrolledPrints,
+                    "Fingerprint is not enrolled".to_string(),
+                ));
+            }
+            Some(finger) => vec![finger],
+            None => enrolled,
+        };
+
+        for finger in fingers {
+            if self.dev.has_feature(FpDeviceFeature::STORAGE_DELETE)
+                && let Ok(Some(print)) = self.store.load(&self.dev, user, finger)
+            {
+                match self.dev.delete_print(&print).await {
+                    Ok(()) | Err(FpError::DataNotFound(_)) => {}
+                    Err(e) => return Err((ServiceError::PrintsNotDeleted, e.to_string())),
+                }
+            }
+            self.store
+                .delete(&self.dev, user, finger)
+                .map_err(store_error)?;
+        }
+        Ok(())
+    }
+}
+
+fn emit(connection: &DBusConnection, path: &str, signal: &str, args: Variant) {
+    let _ = connection.emit_signal(None, path, DEVICE_INTERFACE, signal, Some(&args));
+}
@@ -0,0 +1,340 @@
+//! fprintd compatible D-Bus service.
+//!
+//! `FprintService` exports the `net.reactivated.Fprint.Manager` and `net.reactivated.Fprint.Device` interfaces on a
+//! connection, so that existing fprintd clients (`fprintd-enroll`, `pam_fprintd`, desktop settings) can use the
+//! devices of a `FpContext`, with the prints kept in a `PrintStore`.
+//!
+//! Polkit is not used: a caller may act on behalf of another user only if it runs as root or as the same user as the
+//! service.
+mod device;
+
+use std::{fs, os::unix::fs::MetadataExt, rc::Rc};
+
+use gio::{
+    DBusCallFlags, DBusConnection, DBusMethodInvocation, DBusNodeInfo, RegistrationId, prelude::*,
+};
+use glib::{VariantTy, variant::ObjectPath};
+
+use crate::{FpContext, FpError, FpFinger, PrintStore, store::user_name};
+
+use device::Device;
+
+const MANAGER_INTERFACE: &str = "net.reactivated.Fprint.Manager";
+const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+const ERROR_PREFIX: &str = "net.reactivated.Fprint.Error";
+
+const INTROSPECTION: &str = r#"
+<node>
+  <interface name="net.reactivated.Fprint.Manager">
+    <method name="GetDevices">
+      <arg type="ao" name="devices" direction="out"/>
+    </method>
+    <method name="GetDefaultDevice">
+      <arg type="o" name="device" direction="out"/>
+    </method>
+  </interface>
+  <interface name="net.reactivated.Fprint.Device">
+    <method name="ListEnrolledFingers">
+      <arg type="s" name="username" direction="in"/>
+      <arg type="as" name="enrolled_fingers" direction="out"/>
+    </method>
+    <method name="DeleteEnrolledFingers">
+      <arg type="s" name="username" direction="in"/>
+    </method>
+    <method name="DeleteEnrolledFingers2"/>
+    <method name="DeleteEnrolledFinger">
+      <arg type="s" name="finger_name" direction="in"/>
+    </method>
+    <method name="Claim">
+      <arg type="s" name="username" direction="in"/>
+    </method>
+    <method name="Release"/>
+    <method name="VerifyStart">
+      <arg type="s" name="finger_name" direction="in"/>
+    </method>
+    <method name="VerifyStop"/>
+    <method name="EnrollStart">
+      <arg type="s" name="finger_name" direction="in"/>
+    </method>
+    <method name="EnrollStop"/>
+    <signal name="VerifyFingerSelected">
+      <arg type="s" name="finger_name"/>
+    </signal>
+    <signal name="VerifyStatus">
+      <arg type="s" name="result"/>
+      <arg type="b" name="done"/>
+    </signal>
+    <signal name="EnrollStatus">
+      <arg type="s" name="result"/>
+      <arg type="b" name="done"/>
+    </signal>
+    <property name="name" type="s" access="read"/>
+    <property name="num-enroll-stages" type="i" access="read"/>
+    <property name="scan-type" type="s" access="read"/>
+    <property name="finger-present" type="b" access="read"/>
+    <property name="finger-needed" type="b" access="read"/>
+  </interface>
+</node>
+"#;
+
+/// fprintd finger names, indexed like `FpFinger`.
+const FINGER_NAMES: [(FpFinger, &str); 10] = [
+    (FpFinger::LeftThumb, "left-thumb"),
+    (FpFinger::LeftIndex, "left-index-finger"),
+    (FpFinger::LeftMiddle, "left-middle-finger"),
+    (FpFinger::LeftRing, "left-ring-finger"),
+    (FpFinger::LeftLittle, "left-little-finger"),
+    (FpFinger::RightThumb, "right-thumb"),
+    (FpFinger::RightIndex, "right-index-finger"),
+    (FpFinger::RightMiddle, "right-middle-finger"),
+    (FpFinger::RightRing, "right-ring-finger"),
+    (FpFinger::RightLittle, "right-little-finger"),
+];
+
+/// The fprintd D-Bus service, exported on a connection until it is dropped.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FileStore, FpContext, dbus::FprintService};
+///
+/// let connection = gio::bus_get_sync(gio::BusType::System, gio::Cancellable::NONE).unwrap();
+/// let context = FpContext::new();
+/// let _service = FprintService::register(&connection, &context, FileStore::system()).unwrap();
+/// gio::bus_own_name_on_connection(
+///     &connection,
+///     FprintService::BUS_NAME,
+///     gio::BusNameOwnerFlags::NONE,
+///     |_, _| {},
+///     |_, _| {},
+/// );
+/// glib::MainLoop::new(None, false).run();
+/// ```
+pub struct FprintService {
+    connection: DBusConnection,
+    registrations: Vec<RegistrationId>,
+    devices: Vec<Rc<Device>>,
+}
+
+impl FprintService {
+    /// Well known bus name of fprintd.
+    pub const BUS_NAME: &'static str = "net.reactivated.Fprint";
+    /// Object path of the manager.
+    pub const MANAGER_PATH: &'static str = "/net/reactivated/Fprint/Manager";
+
+    /// Exports the manager and one device object for each device of `context`, found at registration time, on
+    /// `connection`. Owning `BUS_NAME` is left to the caller.
+    ///
+    /// The service must be used from the thread default main context, which must be running for the requests to be
+    /// handled.
+    pub fn register(
+        connection: &DBusConnection,
+        context: &FpContext,
+        store: impl PrintStore + 'static,
+    ) -> Result<Self, glib::Error> {
+        let node = DBusNodeInfo::for_xml(INTROSPECTION)?;
+        let manager = node.lookup_interface(MANAGER_INTERFACE).unwrap();
+        let interface = node.lookup_interface(DEVICE_INTERFACE).unwrap();
+        let store: Rc<dyn PrintStore> = Rc::new(store);
+
+        let mut service = Self {
+            connection: connection.clone(),
+            registrations: Vec::new(),
+            devices: Vec::new(),
+        };
+        for (i, dev) in context.devices().into_iter().enumerate() {
+            let path = format!("/net/reactivated/Fprint/Device/{}", i);
+            let device = Device::new(dev, path, connection, store.clone());
+            service
+                .registrations
+                .push(device.register(connection, &interface)?);
+            service.devices.push(device);
+        }
+
+        let paths: Vec<ObjectPath> = service
+            .device_paths()
+            .into_iter()
+            .map(|path| ObjectPath::try_from(path).unwrap())
+            .collect();
+        let id = connection
+            .register_object(Self::MANAGER_PATH, &manager)
+            .method_call(move |_, _, _, _, method, _, invocation| match method {
+                "GetDevices" => invocation.return_value(Some(&(paths.clone(),).to_variant())),
+                "GetDefaultDevice" => match paths.first() {
+                    Some(path) => invocation.return_value(Some(&(path.clone(),).to_variant())),
+                    None => ServiceError::NoSuchDevice.reply(invocation, "No devices available"),
+                },
+                _ => unreachable!("Method {} is not in the introspection data", method),
+            })
+            .build()?;
+        service.registrations.push(id);
+        Ok(service)
+    }
+
+    /// Object paths of the exported devices.
+    pub fn device_paths(&self) -> Vec<String> {
+        self.devices
+            .iter()
+            .map(|device| device.path().to_string())
+            .collect()
+    }
+}
+
+impl Drop for FprintService {
+    fn drop(&mut self) {
+        for id in self.registrations.drain(..) {
+            let _ = self.connection.unregister_object(id);
+        }
+        for device in &self.devices {
+            device.shutdown();
+        }
+    }
+}
+
+/// Errors returned to the D-Bus callers, named like the ones of fprintd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ServiceError {
+    PermissionDenied,
+    AlreadyInUse,
+    Internal,
+    NoEnrolledPrints,
+    ClaimDevice,
+    NoActionInProgress,
+    InvalidFingername,
+    NoSuchDevice,
+    PrintsNotDeleted,
+}
+
+impl ServiceError {
+    fn name(self) -> &'static str {
+        match self {
+            ServiceError::PermissionDenied => "PermissionDenied",
+            ServiceError::AlreadyInUse => "AlreadyInUse",
+            ServiceError::Internal => "Internal",
+            ServiceError::NoEnrolledPrints => "NoEnrolledPrints",
+            ServiceError::ClaimDevice => "ClaimDevice",
+            ServiceError::NoActionInProgress => "NoActionInProgress",
+            ServiceError::InvalidFingername => "InvalidFingername",
+            ServiceError::NoSuchDevice => "NoSuchDevice",
+            ServiceError::PrintsNotDeleted => "PrintsNotDeleted",
+        }
+    }
+
+    fn reply(self, invocation: DBusMethodInvocation, message: &str) {
+        invocation.return_dbus_error(&format!("{}.{}", ERROR_PREFIX, self.name()), message);
+    }
+}
+
+fn finger_name(finger: FpFinger) -> Option<&'static str> {
+    FINGER_NAMES
+        .iter()
+        .find(|(f, _)| *f == finger)
+        .map(|(_, name)| *name)
+}
+
+fn parse_finger(name: &str) -> Option<FpFinger> {
+    FINGER_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(finger, _)| *finger)
+}
+
+/// `VerifyStatus` result of a failed verification.
+fn verify_status(error: &FpError) -> &'static str {
+    match error {
+        FpError::RetryGeneral(_) => "verify-retry-scan",
+        FpError::RetryTooShort(_) => "verify-swipe-too-short",
+        FpError::RetryCenterFinger(_) => "verify-finger-not-centered",
+        FpError::RetryRemoveFinger(_) => "verify-remove-and-retry",
+        FpError::Removed(_) => "verify-disconnected",
+        _ => "verify-unknown-error",
+    }
+}
+
+/// `EnrollStatus` result of a failed enrollment stage.
+fn enroll_status(error: &FpError) -> &'static str {
+    match error {
+        FpError::RetryGeneral(_) => "enroll-retry-scan",
+        FpError::RetryTooShort(_) => "enroll-swipe-too-short",
+        FpError::RetryCenterFinger(_) => "enroll-finger-not-centered",
+        FpError::RetryRemoveFinger(_) => "enroll-remove-and-retry",
+        FpError::DataFull(_) => "enroll-data-full",
+        FpError::DataDuplicate(_) => "enroll-duplicate",
+        FpError::Removed(_) => "enroll-disconnected",
+        FpError::General(_) | FpError::Proto(_) | FpError::DataInvalid(_) => "enroll-failed",
+        _ => "enroll-unknown-error",
+    }
+}
+
+/// Unix user ID of the process owning the `sender` bus name.
+async fn caller_uid(connection: &DBusConnection, sender: &str) -> Result<u32, glib::Error> {
+    let reply = connection
+        .call_future(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetConnectionUnixUser",
+            Some(&(sender,).to_variant()),
+            Some(VariantTy::new("(u)").unwrap()),
+            DBusCallFlags::NONE,
+            -1,
+        )
+        .await?;
+    Ok(reply.get::<(u32,)>().unwrap().0)
+}
+
+/// Resolves the user a request of `sender` acts on: its own user when `requested` is empty, or `requested` if the
+/// caller is allowed to act on behalf of other users.
+async fn resolve_user(
+    connection: &DBusConnection,
+    sender: &str,
+    requested: &str,
+) -> Result<String, (ServiceError, String)> {
+    let uid = caller_uid(connection, sender)
+        .await
+        .map_err(|e| (ServiceError::Internal, e.message().to_string()))?;
+    let caller = user_name(uid);
+    if requested.is_empty() || caller.as_deref() == Some(requested) {
+        return caller.ok_or_else(|| {
+            (
+                ServiceError::Internal,
+                format!("Unknown user for uid {}", uid),
+            )
+        });
+    }
+
+    let service_uid = fs::metadata("/proc/self").map(|meta| meta.uid()).ok();
+    if uid == 0 || Some(uid) == service_uid {
+        Ok(requested.to_string())
+    } else {
+        Err((
+            ServiceError::PermissionDenied,
+            format!("Not allowed to act on behalf of {}", requested),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FINGER_NAMES, enroll_status, finger_name, parse_finger, verify_status};
+    use crate::{FpError, FpFinger};
+
+    #[test]
+    fn finger_names() {
+        for (finger, name) in FINGER_NAMES {
+            assert_eq!(finger_name(finger), Some(name));
+            assert_eq!(parse_finger(name), Some(finger));
+        }
+        assert_eq!(finger_name(FpFinger::Unknown), None);
+        assert_eq!(parse_finger("right-index"), None);
+    }
+
+    #[test]
+    fn status_names() {
+        let retry = FpError::RetryTooShort(String::new());
+        assert_eq!(verify_status(&retry), "verify-swipe-too-short");
+        assert_eq!(enroll_status(&retry), "enroll-swipe-too-short");
+        assert_eq!(
+            enroll_status(&FpError::DataFull(String::new())),
+            "enroll-data-full"
+        );
+    }
+}
@@ -50,13 +50,14 @@ pub(crate) extern "C" fn fp_match_cb<F, T>(
     user_data: *mut c_void,
     error: *mut libfprint_sys::GError,
 ) where
-    F: Fn(&FpDevice, Option<FpPrint>, FpPrint, Option<glib::Error>, &Option<T>),
+    F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<glib::Error>, &Option<T>),
 {
     if !user_data.is_null() {
         // We reconstruct the UserData struct from the pointer
         // Safety: We are the only ones who have access to the pointer,
         // which is created either at verify_sync or identify_sync. Either way, the pointer is
-        // forgotten after the callback is called, so we aer the only "owners" of the pointer.
+        // forgotten after the callback is called, since libfprint may call us more than once
+        // (i.e on retry errors). The pointer is dropped by the caller once the operation is done.
         let callback_data: Arc<UserData<F, T>> = unsafe { Arc::from_raw(user_data.cast()) };
 
         let device = unsafe { FpDevice::from_glib_none(device) };
@@ -65,12 +66,28 @@ pub(crate) extern "C" fn fp_match_cb<F, T>(
             true => None,
             false => Some(unsafe { FpPrint::from_glib_none(match_print) }),
         };
-        let print = unsafe { FpPrint::from_glib_none(print) };
+        // The scanned print is NULL when reporting a retry or an error
+        let print = match print.is_null() {
+            true => None,
+            false => Some(unsafe { FpPrint::from_glib_none(print) }),
+        };
         let error = match error.is_null() {
             true => None,
             false => Some(unsafe { glib::Error::from_glib_none(error.cast()) }),
         };
 
         callback_data.callback_match(&device, match_print, print, error);
+
+        std::mem::forget(callback_data);
+    }
+}
+
+/// `GDestroyNotify` used by the asynchronous operations to release the `UserData`
+/// created by `fn_pointer!` once libfprint is done with the callback.
+pub(crate) extern "C" fn drop_user_data<F, T>(user_data: *mut c_void) {
+    if !user_data.is_null() {
+        // Safety: The pointer was created by `fn_pointer!` and libfprint calls the destroy
+        // notify exactly once, after the last invocation of the callback.
+        let _: Arc<UserData<F, T>> = unsafe { Arc::from_raw(user_data.cast()) };
     }
 }
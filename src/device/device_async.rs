@@ -0,0 +1,681 @@
+use std::{future::Future, pin::Pin};
+
+use crate::device::{
+    callback::{drop_user_data, fp_enroll_progress, fp_match_cb},
+    enums::FpDeviceFeature,
+    fn_pointer,
+};
+use crate::image::FpImage;
+use crate::print::{EnrollTemplate, FpPrint};
+use gio::Cancellable;
+use glib::object::{ObjectExt, ObjectType};
+use glib::thread_guard::ThreadGuard;
+use glib::translate::{FromGlibPtrContainer, FromGlibPtrFull, ToGlibContainerFromSlice, ToGlibPtr};
+
+use super::FpDevice;
+
+/// Boxed future returned by the asynchronous `FpDevice` operations.
+pub type FpFuture<T> = Pin<Box<dyn Future<Output = Result<T, crate::FpError>> + 'static>>;
+
+/// Function used to collect the result of an asynchronous operation on a `O` from its `GAsyncResult`.
+type FinishFn<O, R> = fn(*mut O, *mut libfprint_sys::GAsyncResult) -> R;
+
+struct AsyncData<O, R, P> {
+    finish: FinishFn<O, R>,
+    callback: ThreadGuard<P>,
+}
+
+unsafe extern "C" fn async_trampoline<O, R, P: FnOnce(R) + 'static>(
+    source_object: *mut libfprint_sys::GObject,
+    res: *mut libfprint_sys::GAsyncResult,
+    user_data: libfprint_sys::gpointer,
+) {
+    // Safety: The pointer was created in `start_async` and libfprint calls the
+    // ready callback exactly once, so we are the only owners of the data.
+    let data: Box<AsyncData<O, R, P>> = unsafe { Box::from_raw(user_data.cast()) };
+    let result = (data.finish)(source_object.cast(), res);
+    (data.callback.into_inner())(result);
+}
+
+/// Starts an asynchronous libfprint operation on a `O`, such as a device or an image. `start`
+/// receives the ready callback and its user data, `finish` is run once the operation completes
+/// and its result is passed to `callback`.
+pub(crate) fn start_async<O, R, P, S>(start: S, finish: FinishFn<O, R>, callback: P)
+where
+    O: 'static,
+    R: 'static,
+    P: FnOnce(R) + 'static,
+    S: FnOnce(libfprint_sys::GAsyncReadyCallback, libfprint_sys::gpointer),
+{
+    let main_context = glib::MainContext::ref_thread_default();
+    let is_main_context_owner = main_context.is_owner();
+    let has_acquired_main_context = (!is_main_context_owner)
+        .then(|| main_context.acquire().ok())
+        .flatten();
+    assert!(
+        is_main_context_owner || has_acquired_main_context.is_some(),
+        "Async operations only allowed if the thread is owning the MainContext"
+    );
+
+    let data = Box::new(AsyncData {
+        finish,
+        callback: ThreadGuard::new(callback),
+    });
+    start(
+        Some(async_trampoline::<O, R, P>),
+        Box::into_raw(data) as libfprint_sys::gpointer,
+    );
+}
+
+/// Converts the outcome of a `*_finish` call returning a `gboolean` into a `Result`.
+fn bool_result(res: i32, error: *mut libfprint_sys::GError) -> Result<(), crate::FpError> {
+    if res == glib::ffi::GFALSE {
+        return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+    }
+    Ok(())
+}
+
+impl FpDevice {
+    /// Starts an asynchronous operation on the device, see `start_async`.
+    fn start_async<R, P, S>(
+        &self,
+        start: S,
+        finish: FinishFn<libfprint_sys::FpDevice, R>,
+        callback: P,
+    ) where
+        R: 'static,
+        P: FnOnce(R) + 'static,
+        S: FnOnce(libfprint_sys::GAsyncReadyCallback, libfprint_sys::gpointer),
+    {
+        start_async(start, finish, callback);
+    }
+
+    fn open_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_open(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok = unsafe { libfprint_sys::fp_device_open_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    fn close_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_close(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok = unsafe { libfprint_sys::fp_device_close_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    pub(crate) fn enroll_async<F, T, P>(
+        &self,
+        template: FpPrint,
+        cancellable: &Cancellable,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+        callback: P,
+    ) where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+        P: FnOnce(Result<FpPrint, crate::FpError>) + 'static,
+    {
+        let template = self.check_print(template);
+        self.enroll_template_async(template, cancellable, progress_cb, progress_data, callback);
+    }
+
+    /// Runs `fp_device_enroll` with `template` as is.
+    fn enroll_template_async<F, T, P>(
+        &self,
+        template: FpPrint,
+        cancellable: &Cancellable,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+        callback: P,
+    ) where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+        P: FnOnce(Result<FpPrint, crate::FpError>) + 'static,
+    {
+        let user_ptr = fn_pointer!(progress_cb, progress_data);
+
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_enroll(
+                    self.to_glib_none().0,
+                    template.to_glib_full(),
+                    cancellable.as_ptr().cast(),
+                    Some(fp_enroll_progress::<F, T>),
+                    user_ptr,
+                    Some(drop_user_data::<F, T>),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ptr = unsafe { libfprint_sys::fp_device_enroll_finish(dev, res, &mut error) };
+                if ptr.is_null() {
+                    return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+                }
+                let fp = unsafe { FpPrint::from_glib_full(ptr) };
+                unsafe {
+                    fp.set_data("set", true);
+                }
+                Ok(fp)
+            },
+            callback,
+        );
+    }
+
+    pub(crate) fn verify_async<F, T, P>(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: &Cancellable,
+        match_cb: Option<F>,
+        match_data: Option<T>,
+        callback: P,
+    ) where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+        P: FnOnce(Result<(bool, Option<FpPrint>), crate::FpError>) + 'static,
+    {
+        let user_ptr = fn_pointer!(match_cb, match_data);
+
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_verify(
+                    self.to_glib_none().0,
+                    enrolled_print.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    Some(fp_match_cb::<F, T>),
+                    user_ptr,
+                    Some(drop_user_data::<F, T>),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let mut matched = glib::ffi::GFALSE;
+                let mut print = std::ptr::null_mut();
+                let ok = unsafe {
+                    libfprint_sys::fp_device_verify_finish(
+                        dev,
+                        res,
+                        &mut matched,
+                        &mut print,
+                        &mut error,
+                    )
+                };
+                let print = (!print.is_null()).then(|| unsafe { FpPrint::from_glib_full(print) });
+                bool_result(ok, error)?;
+                Ok((matched == glib::ffi::GTRUE, print))
+            },
+            callback,
+        );
+    }
+
+    pub(crate) fn identify_async<F, T, P>(
+        &self,
+        prints: &[FpPrint],
+        cancellable: &Cancellable,
+        match_cb: Option<F>,
+        match_data: Option<T>,
+        callback: P,
+    ) where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+        P: FnOnce(Result<(Option<FpPrint>, Option<FpPrint>), crate::FpError>) + 'static,
+    {
+        let user_ptr = fn_pointer!(match_cb, match_data);
+        // The array is referenced by libfprint for the duration of the operation, so the
+        // elements must hold a reference of their own.
+        let raw_prints: *mut glib::ffi::GPtrArray =
+            ToGlibContainerFromSlice::to_glib_full_from_slice(prints);
+        unsafe {
+            glib::ffi::g_ptr_array_set_free_func(
+                raw_prints,
+                Some(std::mem::transmute::<
+                    unsafe extern "C" fn(*mut glib::gobject_ffi::GObject),
+                    unsafe extern "C" fn(glib::ffi::gpointer),
+                >(glib::gobject_ffi::g_object_unref)),
+            )
+        };
+
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_identify(
+                    self.to_glib_none().0,
+                    raw_prints.cast(),
+                    cancellable.as_ptr().cast(),
+                    Some(fp_match_cb::<F, T>),
+                    user_ptr,
+                    Some(drop_user_data::<F, T>),
+                    cb,
+                    data,
+                );
+                glib::ffi::g_ptr_array_unref(raw_prints);
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let mut print_match = std::ptr::null_mut();
+                let mut print = std::ptr::null_mut();
+                let ok = unsafe {
+                    libfprint_sys::fp_device_identify_finish(
+                        dev,
+                        res,
+                        &mut print_match,
+                        &mut print,
+                        &mut error,
+                    )
+                };
+                let print_match = (!print_match.is_null())
+                    .then(|| unsafe { FpPrint::from_glib_full(print_match) });
+                let print = (!print.is_null()).then(|| unsafe { FpPrint::from_glib_full(print) });
+                bool_result(ok, error)?;
+                Ok((print_match, print))
+            },
+            callback,
+        );
+    }
+
+    fn capture_async<P: FnOnce(Result<FpImage, crate::FpError>) + 'static>(
+        &self,
+        wait_for_finger: bool,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_capture(
+                    self.to_glib_none().0,
+                    wait_for_finger as i32,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let image =
+                    unsafe { libfprint_sys::fp_device_capture_finish(dev, res, &mut error) };
+                if image.is_null() {
+                    return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+                }
+                Ok(unsafe { FpImage::from_glib_full(image) })
+            },
+            callback,
+        );
+    }
+
+    fn list_prints_async<P: FnOnce(Result<Vec<FpPrint>, crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_list_prints(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let prints =
+                    unsafe { libfprint_sys::fp_device_list_prints_finish(dev, res, &mut error) };
+                if prints.is_null() {
+                    return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+                }
+                Ok(unsafe {
+                    FromGlibPtrContainer::from_glib_container(prints.cast::<glib::ffi::GPtrArray>())
+                })
+            },
+            callback,
+        );
+    }
+
+    fn delete_print_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_delete_print(
+                    self.to_glib_none().0,
+                    enrolled_print.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok =
+                    unsafe { libfprint_sys::fp_device_delete_print_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    fn clear_storage_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_clear_storage(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok =
+                    unsafe { libfprint_sys::fp_device_clear_storage_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    fn suspend_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_suspend(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok = unsafe { libfprint_sys::fp_device_suspend_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    fn resume_async<P: FnOnce(Result<(), crate::FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        self.start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_device_resume(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |dev, res| {
+                let mut error = std::ptr::null_mut();
+                let ok = unsafe { libfprint_sys::fp_device_resume_finish(dev, res, &mut error) };
+                bool_result(ok, error)
+            },
+            callback,
+        );
+    }
+
+    #[cfg(not(doctest))]
+    /// Open the device asynchronously.
+    ///
+    /// The returned future must be driven by the thread default `glib::MainContext`, dropping it
+    /// cancels the operation.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open().await.unwrap();
+    ///     dev.close().await.unwrap();
+    /// });
+    /// ```
+    pub fn open(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.open_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Close the device asynchronously.
+    pub fn close(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.close_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    #[cfg(not(doctest))]
+    /// Enroll a new print asynchronously.
+    ///
+    /// `progress_cb` will be called for each stage of the enrollment process. See `FpDevice::enroll_sync` for the
+    /// templates accepted.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{EnrollTemplate, FpContext, FpEnrollProgress, FpFinger};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open().await.unwrap();
+    ///     let template = EnrollTemplate::builder(dev)
+    ///         .finger(FpFinger::RightIndex)
+    ///         .username("bruce")
+    ///         .build()
+    ///         .unwrap();
+    ///     let print = dev
+    ///         .enroll(template, None::<FpEnrollProgress<()>>, None)
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// ```
+    pub fn enroll<F, T>(
+        &self,
+        template: impl Into<EnrollTemplate>,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> FpFuture<FpPrint>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+        T: 'static,
+    {
+        let template = template.into().into_print();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.enroll_async(
+                template,
+                cancellable,
+                progress_cb,
+                progress_data,
+                move |res| send.resolve(res),
+            );
+        }))
+    }
+
+    /// Update an enrolled print with new scans asynchronously.
+    ///
+    /// Requires the device to support `FpDeviceFeature::UPDATE_PRINT`, see `FpDevice::update_print_sync`.
+    pub fn update_print<F, T>(
+        &self,
+        print: &FpPrint,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> FpFuture<FpPrint>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+        T: 'static,
+    {
+        let print = print.clone();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            if let Err(e) = obj.require_feature(FpDeviceFeature::UPDATE_PRINT) {
+                send.resolve(Err(e));
+                return;
+            }
+            obj.enroll_template_async(print, cancellable, progress_cb, progress_data, move |res| {
+                send.resolve(res)
+            });
+        }))
+    }
+
+    /// Verify a given print asynchronously.
+    ///
+    /// Resolves to whether the scanned finger matched `enrolled_print`, alongside the newly
+    /// scanned print if the driver provides one.
+    pub fn verify<F, T>(
+        &self,
+        enrolled_print: &FpPrint,
+        match_cb: Option<F>,
+        match_data: Option<T>,
+    ) -> FpFuture<(bool, Option<FpPrint>)>
+    where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+        T: 'static,
+    {
+        let enrolled_print = enrolled_print.clone();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.verify_async(
+                &enrolled_print,
+                cancellable,
+                match_cb,
+                match_data,
+                move |res| send.resolve(res),
+            );
+        }))
+    }
+
+    /// Identify a print asynchronously.
+    ///
+    /// Resolves to the matching print from `prints`, if any, alongside the newly scanned print
+    /// if the driver provides one.
+    pub fn identify<F, T>(
+        &self,
+        prints: &[FpPrint],
+        match_cb: Option<F>,
+        match_data: Option<T>,
+    ) -> FpFuture<(Option<FpPrint>, Option<FpPrint>)>
+    where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+        T: 'static,
+    {
+        let prints = prints.to_vec();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.identify_async(&prints, cancellable, match_cb, match_data, move |res| {
+                send.resolve(res)
+            });
+        }))
+    }
+
+    /// Capture an image asynchronously.
+    pub fn capture(&self, wait_for_finger: bool) -> FpFuture<FpImage> {
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            obj.capture_async(wait_for_finger, cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// List device stored prints asynchronously.
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_LIST`.
+    pub fn list_prints(&self) -> FpFuture<Vec<FpPrint>> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            if let Err(e) = obj.require_feature(FpDeviceFeature::STORAGE_LIST) {
+                send.resolve(Err(e));
+                return;
+            }
+            obj.list_prints_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Delete a given print from the device asynchronously.
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_DELETE`.
+    pub fn delete_print(&self, enrolled_print: &FpPrint) -> FpFuture<()> {
+        let enrolled_print = enrolled_print.clone();
+        Box::pin(gio::GioFuture::new(self, move |obj, cancellable, send| {
+            if let Err(e) = obj.require_feature(FpDeviceFeature::STORAGE_DELETE) {
+                send.resolve(Err(e));
+                return;
+            }
+            obj.delete_print_async(&enrolled_print, cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Clear sensor storage asynchronously.
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_CLEAR`.
+    pub fn clear_storage(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            if let Err(e) = obj.require_feature(FpDeviceFeature::STORAGE_CLEAR) {
+                send.resolve(Err(e));
+                return;
+            }
+            obj.clear_storage_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Prepare device for suspend asynchronously.
+    pub fn suspend(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.suspend_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+
+    /// Resume device after suspend asynchronously.
+    pub fn resume(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.resume_async(cancellable, move |res| send.resolve(res));
+        }))
+    }
+}
@@ -1,21 +1,26 @@
-use crate::device::{UserData, callback::fp_match_cb, fn_pointer};
+use crate::device::{UserData, callback::fp_match_cb, enums::FpDeviceFeature, fn_pointer};
 use crate::image::FpImage;
 use gio::Cancellable;
 use glib::object::ObjectExt;
-use glib::translate::FromGlibPtrNone;
 use glib::translate::{FromGlibPtrFull, ToGlibPtr};
 use std::sync::Arc;
 
-use crate::print::FpPrint;
+use crate::print::{EnrollTemplate, FpPrint};
 
 use super::FpDevice;
 
 /// This type represents the callback function for the `FpDevice::enroll` implementation and will be called for each stage of the enrollment process.
+///
+/// The enroll operations accept any closure with this signature, so state such as a channel sender can be captured directly
+/// instead of being passed as user data. This alias is mostly useful to name the callback type when none is given, i.e
+/// `None::<FpEnrollProgress<()>>`.
 pub type FpEnrollProgress<T> =
     fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) -> ();
 /// This type represents the callback function for the `FpDevice::verify` and `FpDevice::identify` implementations and will be called when a print is matched.
+///
+/// As with `FpEnrollProgress`, closures with this signature are accepted as well.
 pub type FpMatchCb<T> =
-    fn(&FpDevice, Option<FpPrint>, FpPrint, Option<crate::GError>, &Option<T>) -> ();
+    fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>) -> ();
 
 impl FpDevice {
     #[cfg(not(doctest))]
@@ -30,7 +35,7 @@ impl FpDevice {
     ///
     /// dev.open_sync(None).unwrap();
     /// ```
-    pub fn open_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+    pub fn open_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::FpError> {
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -46,7 +51,7 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
         Ok(())
     }
@@ -63,7 +68,7 @@ impl FpDevice {
     /// dev.open_sync(None).unwrap();
     /// dev.close_sync(None).unwrap();
     /// ```
-    pub fn close_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+    pub fn close_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::FpError> {
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -79,7 +84,7 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
         Ok(())
     }
@@ -87,9 +92,11 @@ impl FpDevice {
     #[cfg(not(doctest))]
     /// Enroll a new print.
     /// Enrolls a print, `progress_cb` will be called for each stage of the enrollment process.
+    ///
+    /// `template` is made with `EnrollTemplate::builder`, which validates its metadata.
     /// # Example:
     /// ```no_run
-    /// use libfprint_rs::{FpDevice, FpContext, FpPrint};
+    /// use libfprint_rs::{EnrollTemplate, FpDevice, FpContext, FpFinger, FpPrint};
     ///
     /// pub fn enroll_cb(device: &FpDevice,enroll_stage: i32, print: Option<FpPrint>, error: Option<libfprint_rs::GError>, data: &Option<i32>,) -> () {
     ///     println!("Enroll stage: {}", enroll_stage);
@@ -100,22 +107,88 @@ impl FpDevice {
     /// let dev = devices.get(0).unwrap();
     /// dev.open_sync(None).unwrap();
     ///
-    /// let template = FpPrint::new(&dev);
+    /// let template = EnrollTemplate::builder(&dev)
+    ///     .finger(FpFinger::RightIndex)
+    ///     .username("bruce")
+    ///     .build()
+    ///     .unwrap();
     /// let new_print = dev.enroll_sync(template, None, Some(enroll_cb), Some(10)).unwrap();
     ///
+    /// // Closures can capture their own state instead of using the user data
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// let template = EnrollTemplate::builder(&dev)
+    ///     .finger(FpFinger::LeftIndex)
+    ///     .username("bruce")
+    ///     .build()
+    ///     .unwrap();
+    /// let progress = move |_: &FpDevice, stage: i32, _: Option<FpPrint>, _: Option<libfprint_rs::GError>, _: &Option<()>| {
+    ///     tx.send(stage).unwrap();
+    /// };
+    /// let new_print = dev.enroll_sync(template, None, Some(progress), None).unwrap();
+    ///
     /// dev.close_sync(None).unwrap();
     /// ```
-    pub fn enroll_sync<T>(
+    pub fn enroll_sync<F, T>(
+        &self,
+        template: impl Into<EnrollTemplate>,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> Result<FpPrint, crate::FpError>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+    {
+        let template = self.check_print(template.into().into_print());
+        self.enroll_template_sync(template, cancellable, progress_cb, progress_data)
+    }
+
+    #[cfg(not(doctest))]
+    /// Update an enrolled print with new scans synchronously, on devices with `FpDeviceFeature::UPDATE_PRINT`.
+    ///
+    /// Unlike `FpDevice::enroll_sync`, the print is passed to the device as is, so that the stored template is
+    /// refreshed rather than replaced. libfprint updates the print in place and the returned print is the same
+    /// object as `print`. Returns `FpError::NotSupported` if the device lacks the feature.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpEnrollProgress, FpPrint};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let print = FpPrint::deserialize(&stored).unwrap();
+    /// let updated = dev
+    ///     .update_print_sync(&print, None, None::<FpEnrollProgress<()>>, None)
+    ///     .unwrap();
+    /// ```
+    pub fn update_print_sync<F, T>(
+        &self,
+        print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> Result<FpPrint, crate::FpError>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+    {
+        self.require_feature(FpDeviceFeature::UPDATE_PRINT)?;
+        self.enroll_template_sync(print.clone(), cancellable, progress_cb, progress_data)
+    }
+
+    /// Runs `fp_device_enroll_sync` with `template` as is.
+    fn enroll_template_sync<F, T>(
         &self,
         template: FpPrint,
         cancellable: Option<&Cancellable>,
-        progress_cb: Option<FpEnrollProgress<T>>,
+        progress_cb: Option<F>,
         progress_data: Option<T>,
-    ) -> Result<FpPrint, crate::GError> {
+    ) -> Result<FpPrint, crate::FpError>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+    {
         let mut error = std::ptr::null_mut();
 
-        let template = self.check_print(template);
-
         let raw_dev = self.to_glib_none().0;
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
@@ -132,15 +205,14 @@ impl FpDevice {
                 raw_dev,
                 raw_template,
                 raw_cancel.cast(),
-                Some(crate::device::callback::fp_enroll_progress::<FpEnrollProgress<T>, T>),
+                Some(crate::device::callback::fp_enroll_progress::<F, T>),
                 user_ptr,
                 std::ptr::addr_of_mut!(error),
             )
         };
 
         if !user_ptr.is_null() {
-            let _: Arc<UserData<FpEnrollProgress<T>, T>> =
-                unsafe { Arc::from_raw(user_ptr.cast()) };
+            let _: Arc<UserData<F, T>> = unsafe { Arc::from_raw(user_ptr.cast()) };
         }
 
         if !ptr.is_null() {
@@ -150,7 +222,7 @@ impl FpDevice {
             }
             Ok(fp)
         } else {
-            Err(unsafe { glib::Error::from_glib_full(error.cast()) })
+            Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into())
         }
     }
 
@@ -161,7 +233,7 @@ impl FpDevice {
     /// ```no_run
     /// use libfprint_rs::{FpDevice, FpContext, FpPrint, GError};
     ///
-    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: FpPrint,
+    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: Option<FpPrint>,
     /// error: Option<GError>, data: &Option<i32>) {
     ///     if matched_print.is_some() {
     ///         println!("Matched print: {:?}", matched_print);
@@ -180,14 +252,19 @@ impl FpDevice {
     ///    println!("Print verified");println
     /// }
     /// ```
-    pub fn verify_sync<T>(
+    pub fn verify_sync<F, T>(
         &self,
         enrolled_print: &FpPrint,
         cancellable: Option<gio::Cancellable>,
-        match_cb: Option<FpMatchCb<T>>,
+        match_cb: Option<F>,
         match_data: Option<T>,
         print: Option<&mut FpPrint>, // TODO: Handle initialized
-    ) -> Result<bool, crate::GError> {
+    ) -> Result<bool, crate::FpError>
+    where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+    {
         let ptr = fn_pointer!(match_cb, match_data);
         let mut error = std::ptr::null_mut();
         let mut matched = glib::ffi::GFALSE;
@@ -208,13 +285,16 @@ impl FpDevice {
                 self.to_glib_none().0,
                 enrolled_print.to_glib_none().0,
                 raw_cancel.cast(),
-                Some(fp_match_cb::<FpMatchCb<T>, T>),
+                Some(fp_match_cb::<F, T>),
                 ptr,
                 &mut matched,
                 new_print_ptr,
                 &mut error,
             )
         };
+        if !ptr.is_null() {
+            let _: Arc<UserData<F, T>> = unsafe { Arc::from_raw(ptr.cast()) };
+        }
         if let Some(p) = print
             && !new_print.is_null()
         {
@@ -224,14 +304,13 @@ impl FpDevice {
         // If res is false, the operation failed, so the `error` pointer must be pointing
         // to a valid error
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_none(error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
         // Else there must be a response
         Ok(matched == glib::ffi::GTRUE)
     }
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     /// Prepare device for suspend.
-    pub fn suspend_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+    pub fn suspend_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::FpError> {
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -247,14 +326,13 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
         Ok(())
     }
 
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     /// Resume device after suspend.
-    pub fn resume_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::GError> {
+    pub fn resume_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), crate::FpError> {
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -270,7 +348,7 @@ impl FpDevice {
             )
         };
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
         Ok(())
     }
@@ -282,7 +360,7 @@ impl FpDevice {
     /// ```no_run
     /// use libfprint_rs::{FpDevice, FpContext, FpPrint, GError};
     ///
-    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: FpPrint,
+    /// pub fn match_cb(device: &FpDevice, matched_print: Option<FpPrint>, enrolled_print: Option<FpPrint>,
     /// error: Option<GError>, data: &Option<i32>) {
     ///     if matched_print.is_some() {
     ///         println!("Matched print: {:?}", matched_print);
@@ -302,14 +380,43 @@ impl FpDevice {
     ///     println!("Found matching print on vector passed");
     /// }
     /// ```
-    pub fn identify_sync<T>(
+    pub fn identify_sync<F, T>(
         &self,
         prints: &[FpPrint],
         cancellable: Option<&Cancellable>,
-        match_cb: Option<FpMatchCb<T>>,
+        match_cb: Option<F>,
         match_data: Option<T>,
         print: Option<&mut FpPrint>, // TODO: Handle initialized
-    ) -> Result<Option<FpPrint>, crate::GError> {
+    ) -> Result<Option<FpPrint>, crate::FpError>
+    where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+    {
+        let (matched, scanned) =
+            self.identify_scanned_sync(prints, cancellable, match_cb, match_data)?;
+        if let Some(p) = print
+            && let Some(scanned) = scanned
+        {
+            *p = scanned;
+        }
+        Ok(matched)
+    }
+
+    /// Identifies like `identify_sync`, returning the matching print from `prints`, if any, alongside the newly
+    /// scanned print if the driver provides one.
+    pub(crate) fn identify_scanned_sync<F, T>(
+        &self,
+        prints: &[FpPrint],
+        cancellable: Option<&Cancellable>,
+        match_cb: Option<F>,
+        match_data: Option<T>,
+    ) -> Result<(Option<FpPrint>, Option<FpPrint>), crate::FpError>
+    where
+        F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<crate::GError>, &Option<T>)
+            + Send
+            + 'static,
+    {
         // Arc the function content and the data, get the pointer. If no function is provided
         // then a null pointer is returned.
 
@@ -326,11 +433,6 @@ impl FpDevice {
         };
 
         let mut new_print: libfprint_sys::FpPrint_autoptr = std::ptr::null_mut();
-        let new_print_ptr = match print {
-            Some(_) => std::ptr::addr_of_mut!(new_print),
-            None => std::ptr::null_mut(),
-        };
-
         let mut print_match = std::ptr::null_mut();
 
         let mut error = std::ptr::null_mut();
@@ -340,29 +442,26 @@ impl FpDevice {
                 self.to_glib_none().0,
                 raw_prints.0.cast(),
                 raw_cancel.cast(),
-                Some(fp_match_cb::<FpMatchCb<T>, T>),
+                Some(fp_match_cb::<F, T>),
                 ptr,
-                new_print_ptr,
+                std::ptr::addr_of_mut!(new_print),
                 std::ptr::addr_of_mut!(print_match),
                 std::ptr::addr_of_mut!(error),
             )
         };
         unsafe { libfprint_sys::g_ptr_array_free(raw_prints.0.cast(), 1) };
+        if !ptr.is_null() {
+            let _: Arc<UserData<F, T>> = unsafe { Arc::from_raw(ptr.cast()) };
+        }
 
-        if let Some(p) = print
-            && !new_print.is_null()
-        {
-            *p = unsafe { FpPrint::from_glib_full(new_print) };
-        };
+        let scanned = (!new_print.is_null()).then(|| unsafe { FpPrint::from_glib_full(new_print) });
 
         if res == glib::ffi::GFALSE {
-            return Err(unsafe { glib::Error::from_glib_full(error.cast()) });
-        }
-        if print_match.is_null() {
-            Ok(None)
-        } else {
-            Ok(Some(unsafe { FpPrint::from_glib_full(print_match) }))
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
         }
+        let matched =
+            (!print_match.is_null()).then(|| unsafe { FpPrint::from_glib_full(print_match) });
+        Ok((matched, scanned))
     }
     #[cfg(not(doctest))]
     /// Start an synchronous operation to capture an image.
@@ -379,7 +478,7 @@ impl FpDevice {
         &self,
         wait_for_finger: bool,
         cancellable: Option<&Cancellable>,
-    ) -> Result<FpImage, crate::GError> {
+    ) -> Result<FpImage, crate::FpError> {
         let raw_cancel = match cancellable {
             Some(p) => p.to_glib_none().0,
             None => std::ptr::null_mut(),
@@ -396,25 +495,151 @@ impl FpDevice {
             )
         };
         if raw_image.is_null() {
-            return Err(unsafe { glib::Error::from_glib_full(raw_error.cast()) });
+            return Err(unsafe { glib::Error::from_glib_full(raw_error.cast()) }.into());
         }
         Ok(unsafe { FpImage::from_glib_full(raw_image) })
     }
 
+    #[cfg(not(doctest))]
     /// Delete a given print from the device.
-    pub fn delete_print_sync() {
-        unimplemented!()
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_DELETE`.
+    /// # Example:
+    /// ```no_run
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// for print in dev.list_prints_sync(None).unwrap() {
+    ///     dev.delete_print_sync(&print, None).unwrap();
+    /// }
+    /// ```
+    pub fn delete_print_sync(
+        &self,
+        enrolled_print: &FpPrint,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<(), crate::FpError> {
+        self.require_feature(FpDeviceFeature::STORAGE_DELETE)?;
+
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let res = unsafe {
+            libfprint_sys::fp_device_delete_print_sync(
+                self.to_glib_none().0,
+                enrolled_print.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if res == glib::ffi::GFALSE {
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+        }
+        Ok(())
     }
+    #[cfg(not(doctest))]
     /// List device stored prints synchronously.
-    pub fn list_prints_sync() {
-        unimplemented!()
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_LIST`.
+    /// # Example:
+    /// ```no_run
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let prints = dev.list_prints_sync(None).unwrap();
+    /// println!("{} prints stored on the device", prints.len());
+    /// ```
+    pub fn list_prints_sync(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<Vec<FpPrint>, crate::FpError> {
+        use glib::translate::FromGlibPtrContainer;
+
+        self.require_feature(FpDeviceFeature::STORAGE_LIST)?;
+
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let raw_prints = unsafe {
+            libfprint_sys::fp_device_list_prints_sync(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if raw_prints.is_null() {
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+        }
+        // The array is transfer container, the prints are owned by the array
+        Ok(unsafe {
+            FromGlibPtrContainer::from_glib_container(raw_prints.cast::<glib::ffi::GPtrArray>())
+        })
     }
+    #[cfg(not(doctest))]
     /// Clear sensor storage.
-    pub fn clear_storage_sync() {
-        unimplemented!()
+    ///
+    /// Requires the device to support `FpDeviceFeature::STORAGE_CLEAR`.
+    /// # Example:
+    /// ```no_run
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// dev.clear_storage_sync(None).unwrap();
+    /// ```
+    pub fn clear_storage_sync(
+        &self,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<(), crate::FpError> {
+        self.require_feature(FpDeviceFeature::STORAGE_CLEAR)?;
+
+        let raw_cancel = match cancellable {
+            Some(p) => p.to_glib_none().0,
+            None => std::ptr::null_mut(),
+        };
+
+        let mut error = std::ptr::null_mut();
+
+        let res = unsafe {
+            libfprint_sys::fp_device_clear_storage_sync(
+                self.to_glib_none().0,
+                raw_cancel.cast(),
+                std::ptr::addr_of_mut!(error),
+            )
+        };
+        if res == glib::ffi::GFALSE {
+            return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+        }
+        Ok(())
+    }
+
+    /// Returns a `FpError::NotSupported` error if the device lacks `feature`.
+    pub(crate) fn require_feature(&self, feature: FpDeviceFeature) -> Result<(), crate::FpError> {
+        if self.has_feature(feature) {
+            return Ok(());
+        }
+        Err(crate::FpError::NotSupported(format!(
+            "Device does not support {}",
+            feature
+        )))
     }
 
-    fn check_print(&self, template: FpPrint) -> FpPrint {
+    /// Returns a fresh template with the metadata of `template` if it is an enrolled print, i.e one returned by an
+    /// enrollment or `FpPrint::deserialize`, so that enrolling with it creates a new print. `update_print_sync` and
+    /// `update_print` bypass this to refresh the enrolled print instead.
+    pub(crate) fn check_print(&self, template: FpPrint) -> FpPrint {
         // This checks if the template was created with FpPrint::new() or not
         let set: Option<bool> = unsafe { template.steal_data("set") };
         if set == Some(true) {
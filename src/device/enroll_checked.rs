@@ -0,0 +1,146 @@
+use gio::Cancellable;
+
+use crate::{
+    FpError,
+    print::{EnrollTemplate, FpPrint},
+};
+
+use super::{FpDevice, FpMatchCb, device_async::FpFuture, enums::FpDeviceFeature};
+
+/// Outcome of `FpDevice::enroll_checked_sync` and `FpDevice::enroll_checked`.
+#[derive(Debug, Clone)]
+pub enum EnrollOutcome {
+    /// The finger was not enrolled yet, this is the new print.
+    Enrolled(FpPrint),
+    /// The finger matched a print of the gallery and nothing was enrolled.
+    Duplicate {
+        /// The print of the gallery matching the finger.
+        existing: FpPrint,
+    },
+    /// The device detected that the finger is already enrolled and nothing was enrolled. The device does not report
+    /// which print matched.
+    DuplicateOnDevice,
+}
+
+impl FpDevice {
+    /// The prints of `gallery` to identify against before enrolling, none if the device checks duplicates itself.
+    /// Returns `FpError::NotSupported` if there are prints to check but the device can not identify.
+    fn duplicate_candidates(&self, gallery: &[FpPrint]) -> Result<Vec<FpPrint>, FpError> {
+        if self.has_feature(FpDeviceFeature::DUPLICATES_CHECK) {
+            return Ok(Vec::new());
+        }
+        let candidates: Vec<FpPrint> = gallery
+            .iter()
+            .filter(|print| print.compatible(self))
+            .cloned()
+            .collect();
+        if !candidates.is_empty() {
+            self.require_feature(FpDeviceFeature::IDENTIFY)?;
+        }
+        Ok(candidates)
+    }
+
+    #[cfg(not(doctest))]
+    /// Enroll a new print synchronously, unless the finger is already enrolled.
+    ///
+    /// Devices with `FpDeviceFeature::DUPLICATES_CHECK` reject duplicates during the enrollment. On other devices,
+    /// the finger is first identified against the prints of `gallery` that are compatible with the device, which
+    /// takes an additional scan, and the enrollment only starts if none matched. Pass the prints of every user so that
+    /// the same finger can not be enrolled twice under different users. If the device neither checks duplicates nor
+    /// supports `FpDeviceFeature::IDENTIFY`, `FpError::NotSupported` is returned before scanning, unless no print of
+    /// `gallery` is compatible with the device.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{
+    ///     EnrollOutcome, EnrollTemplate, FileStore, FpContext, FpEnrollProgress, FpFinger, FpPrint, PrintStore,
+    /// };
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let gallery: Vec<FpPrint> = FileStore::system()
+    ///     .load_all(&dev)
+    ///     .unwrap()
+    ///     .into_iter()
+    ///     .map(|(_, print)| print)
+    ///     .collect();
+    /// let template = EnrollTemplate::builder(&dev)
+    ///     .finger(FpFinger::RightIndex)
+    ///     .username("bruce")
+    ///     .build()
+    ///     .unwrap();
+    /// match dev
+    ///     .enroll_checked_sync(template, &gallery, None, None::<FpEnrollProgress<()>>, None)
+    ///     .unwrap()
+    /// {
+    ///     EnrollOutcome::Enrolled(print) => println!("Enrolled {}", print.finger()),
+    ///     EnrollOutcome::Duplicate { existing } => {
+    ///         println!("This finger is already enrolled by {:?}", existing.username())
+    ///     }
+    ///     EnrollOutcome::DuplicateOnDevice => println!("This finger is already enrolled"),
+    /// }
+    /// ```
+    pub fn enroll_checked_sync<F, T>(
+        &self,
+        template: impl Into<EnrollTemplate>,
+        gallery: &[FpPrint],
+        cancellable: Option<&Cancellable>,
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> Result<EnrollOutcome, FpError>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+    {
+        let candidates = self.duplicate_candidates(gallery)?;
+        if !candidates.is_empty()
+            && let Some(existing) =
+                self.identify_sync(&candidates, cancellable, None::<FpMatchCb<()>>, None, None)?
+        {
+            return Ok(EnrollOutcome::Duplicate { existing });
+        }
+
+        duplicate_outcome(self.enroll_sync(template, cancellable, progress_cb, progress_data))
+    }
+
+    /// Enroll a new print asynchronously, unless the finger is already enrolled.
+    ///
+    /// See `FpDevice::enroll_checked_sync`.
+    pub fn enroll_checked<F, T>(
+        &self,
+        template: impl Into<EnrollTemplate>,
+        gallery: &[FpPrint],
+        progress_cb: Option<F>,
+        progress_data: Option<T>,
+    ) -> FpFuture<EnrollOutcome>
+    where
+        F: Fn(&FpDevice, i32, Option<FpPrint>, Option<crate::GError>, &Option<T>) + Send + 'static,
+        T: 'static,
+    {
+        let device = self.clone();
+        let template = template.into();
+        let candidates = self.duplicate_candidates(gallery);
+        Box::pin(async move {
+            let candidates = candidates?;
+            if !candidates.is_empty()
+                && let (Some(existing), _) = device
+                    .identify(&candidates, None::<FpMatchCb<()>>, None)
+                    .await?
+            {
+                return Ok(EnrollOutcome::Duplicate { existing });
+            }
+
+            duplicate_outcome(device.enroll(template, progress_cb, progress_data).await)
+        })
+    }
+}
+
+/// Maps a duplicate detected by the device to `EnrollOutcome::DuplicateOnDevice`.
+fn duplicate_outcome(res: Result<FpPrint, FpError>) -> Result<EnrollOutcome, FpError> {
+    match res {
+        Ok(print) => Ok(EnrollOutcome::Enrolled(print)),
+        Err(FpError::DataDuplicate(_)) => Ok(EnrollOutcome::DuplicateOnDevice),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_channel::mpsc::{TryRecvError, UnboundedReceiver, UnboundedSender, unbounded};
+use futures_core::Stream;
+use gio::{Cancellable, prelude::CancellableExt};
+use glib::thread_guard::ThreadGuard;
+
+use crate::{
+    FpError, FpRetryReason,
+    print::{EnrollTemplate, FpPrint},
+};
+
+use super::FpDevice;
+
+/// Event emitted by an `EnrollStream` while an enrollment is in progress.
+#[derive(Debug, Clone)]
+pub enum EnrollEvent {
+    /// An enroll stage was completed.
+    Progress {
+        /// Number of completed stages.
+        completed_stages: i32,
+        /// Total number of stages required by the device, see `FpDevice::nr_enroll_stage`.
+        total_stages: i32,
+        /// The last scanned print, if the driver provides one.
+        print: Option<FpPrint>,
+    },
+    /// The last scan failed and the current stage has to be retried.
+    Retry {
+        /// Number of completed stages.
+        completed_stages: i32,
+        /// Total number of stages required by the device, see `FpDevice::nr_enroll_stage`.
+        total_stages: i32,
+        /// Why the scan has to be retried.
+        reason: FpRetryReason,
+        /// The message reported by the driver.
+        message: String,
+    },
+    /// The enrollment finished successfully. This is the last event of the stream.
+    Completed(FpPrint),
+    /// The enrollment failed. This is the last event of the stream.
+    Failed(FpError),
+}
+
+/// Stream of `EnrollEvent`s for an enrollment in progress, created by `FpDevice::enroll_stream`.
+///
+/// The enrollment is driven by the thread default `glib::MainContext`. The stream can be polled from a future running on
+/// that context, or used as a blocking `Iterator`, in which case the context is iterated until the next event arrives.
+/// Dropping the stream before the enrollment finishes cancels it.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{EnrollEvent, EnrollTemplate, FpContext, FpFinger};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.first().unwrap();
+/// dev.open_sync(None).unwrap();
+///
+/// let template = EnrollTemplate::builder(dev)
+///     .finger(FpFinger::RightIndex)
+///     .username("bruce")
+///     .build()
+///     .unwrap();
+/// for event in dev.enroll_stream(template) {
+///     match event {
+///         EnrollEvent::Progress { completed_stages, total_stages, .. } => {
+///             println!("Stage {}/{}", completed_stages, total_stages)
+///         }
+///         EnrollEvent::Retry { reason, .. } => println!("{}", reason),
+///         EnrollEvent::Completed(_) => println!("Enrolled"),
+///         EnrollEvent::Failed(err) => println!("Enroll failed: {}", err),
+///     }
+/// }
+/// ```
+pub struct EnrollStream {
+    receiver: UnboundedReceiver<EnrollEvent>,
+    cancellable: Cancellable,
+    finished: bool,
+}
+
+impl EnrollStream {
+    /// Cancels the enrollment. A `EnrollEvent::Failed` event is emitted once the device stops.
+    pub fn cancel(&self) {
+        self.cancellable.cancel();
+    }
+
+    fn track(&mut self, event: Option<EnrollEvent>) -> Option<EnrollEvent> {
+        if matches!(
+            event,
+            None | Some(EnrollEvent::Completed(_)) | Some(EnrollEvent::Failed(_))
+        ) {
+            self.finished = true;
+        }
+        event
+    }
+}
+
+impl Stream for EnrollStream {
+    type Item = EnrollEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.receiver).poll_next(cx) {
+            Poll::Ready(event) => Poll::Ready(self.track(event)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Iterator for EnrollStream {
+    type Item = EnrollEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let main_context = glib::MainContext::ref_thread_default();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return self.track(Some(event)),
+                Err(TryRecvError::Closed) => return self.track(None),
+                Err(TryRecvError::Empty) => {
+                    main_context.iteration(true);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for EnrollStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.cancellable.cancel();
+        }
+    }
+}
+
+fn send_event(sender: &UnboundedSender<EnrollEvent>, event: EnrollEvent) {
+    // The receiver might be gone already, in which case nobody is interested in the event
+    let _ = sender.unbounded_send(event);
+}
+
+impl FpDevice {
+    /// Enroll a new print, reporting the progress as a stream of `EnrollEvent`s.
+    ///
+    /// `template` is handled as by `FpDevice::enroll_sync`. See `EnrollStream` for how the stream is driven.
+    /// # Example:
+    /// ```no_run
+    /// use std::pin::Pin;
+    ///
+    /// use futures_core::Stream;
+    /// use libfprint_rs::{EnrollTemplate, FpContext, FpFinger};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.first().unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open().await.unwrap();
+    ///     let template = EnrollTemplate::builder(dev)
+    ///         .finger(FpFinger::RightIndex)
+    ///         .username("bruce")
+    ///         .build()
+    ///         .unwrap();
+    ///     let mut events = dev.enroll_stream(template);
+    ///     while let Some(event) =
+    ///         std::future::poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await
+    ///     {
+    ///         println!("{:?}", event);
+    ///     }
+    /// });
+    /// ```
+    pub fn enroll_stream(&self, template: impl Into<EnrollTemplate>) -> EnrollStream {
+        let (sender, receiver) = unbounded();
+        let cancellable = Cancellable::new();
+        let total_stages = self.nr_enroll_stage();
+
+        // The callback is always invoked on this thread, the guard only makes the
+        // non-`Send` sender satisfy the callback bounds.
+        let progress_sender = ThreadGuard::new(sender.clone());
+        let progress = move |_: &FpDevice,
+                             completed_stages: i32,
+                             print: Option<FpPrint>,
+                             error: Option<crate::GError>,
+                             _: &Option<()>| {
+            let event = match error.map(FpError::from) {
+                Some(error) => match error.retry_reason() {
+                    Some(reason) => EnrollEvent::Retry {
+                        completed_stages,
+                        total_stages,
+                        reason,
+                        message: error.message().to_string(),
+                    },
+                    // Fatal errors are reported once the operation finishes
+                    None => return,
+                },
+                None => EnrollEvent::Progress {
+                    completed_stages,
+                    total_stages,
+                    print,
+                },
+            };
+            send_event(progress_sender.get_ref(), event);
+        };
+
+        self.enroll_async(
+            template.into().into_print(),
+            &cancellable,
+            Some(progress),
+            None,
+            move |res| {
+                let event = match res {
+                    Ok(print) => EnrollEvent::Completed(print),
+                    Err(error) => EnrollEvent::Failed(error),
+                };
+                send_event(&sender, event);
+            },
+        );
+
+        EnrollStream {
+            receiver,
+            cancellable,
+            finished: false,
+        }
+    }
+}
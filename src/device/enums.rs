@@ -1,3 +1,7 @@
+use std::fmt::Display;
+
+use glib::bitflags::bitflags;
+
 /// The scan type of the device.
 #[derive(Debug, Clone, Copy)]
 pub enum FpScanType {
@@ -5,55 +9,115 @@ pub enum FpScanType {
     Press = libfprint_sys::FpScanType_FP_SCAN_TYPE_PRESS as isize,
 }
 
-/// The finger status flags for the device.
-#[derive(Debug, Clone, Copy)]
-pub enum FpFingerStatus {
-    None = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NONE as isize,
-    Needed = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED as isize,
-    Present = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT as isize,
+/// The thermal state of the device. Devices that are too hot refuse to start new operations until they cool down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FpTemperature {
+    /// Sensor is considered cold.
+    Cold,
+    /// Sensor is warm, usually no action is needed.
+    Warm,
+    /// Sensor is hot, operations are refused until it cools down.
+    Hot,
+    /// A state added by a newer libfprint version, with its raw value. Handled like `FpTemperature::Hot` by
+    /// `FpDevice::wait_for_cool_down`.
+    Unknown(u32),
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum FpDeviceFeature {
-    /// Device does not support any feature
-    None = 0,
-    /// Supports image capture
-    Capture = 1,
-    /// Supports finger identification
-    Identify = 2,
-    /// Supports finger verification
-    Verify = 4,
-    /// Device has a persistent storage
-    Storage = 8,
-    /// Supports listing the storage templates
-    StorageList = 16,
-    /// Supports deleting stored templates
-    StorageDelete = 32,
-    /// Supports clearing the whole storage
-    StorageClear = 64,
-    /// Natively supports duplicates detection
-    DuplicatesCheck = 128,
-    /// Whether the device can run continuously
-    AlwaysOn = 256,
-    /// Supports updating an existing print record using new scans
-    UpdatePrint = 512,
-}
-
-impl FpDeviceFeature {
-    pub fn try_from(n: u32) -> Result<Self, ()> {
-        match n {
-            0 => Ok(FpDeviceFeature::None),
-            1 => Ok(FpDeviceFeature::Capture),
-            2 => Ok(FpDeviceFeature::Identify),
-            4 => Ok(FpDeviceFeature::Verify),
-            8 => Ok(FpDeviceFeature::Storage),
-            16 => Ok(FpDeviceFeature::StorageList),
-            32 => Ok(FpDeviceFeature::StorageDelete),
-            64 => Ok(FpDeviceFeature::StorageClear),
-            128 => Ok(FpDeviceFeature::DuplicatesCheck),
-            256 => Ok(FpDeviceFeature::AlwaysOn),
-            512 => Ok(FpDeviceFeature::UpdatePrint),
-            _ => Err(()),
+bitflags! {
+    /// The finger status flags for the device. An empty set means no finger is needed nor present.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FpFingerStatus: u32 {
+        /// The sensor is waiting for a finger
+        const NEEDED = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED;
+        /// A finger is present on the sensor
+        const PRESENT = libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT;
+    }
+}
+
+bitflags! {
+    /// Set of features supported by a device. An empty set means the device does not support any feature.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FpDeviceFeature: u32 {
+        /// Supports image capture
+        const CAPTURE = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_CAPTURE;
+        /// Supports finger identification
+        const IDENTIFY = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_IDENTIFY;
+        /// Supports finger verification
+        const VERIFY = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_VERIFY;
+        /// Device has a persistent storage
+        const STORAGE = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_STORAGE;
+        /// Supports listing the storage templates
+        const STORAGE_LIST = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_STORAGE_LIST;
+        /// Supports deleting stored templates
+        const STORAGE_DELETE = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_STORAGE_DELETE;
+        /// Supports clearing the whole storage
+        const STORAGE_CLEAR = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_STORAGE_CLEAR;
+        /// Natively supports duplicates detection
+        const DUPLICATES_CHECK = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_DUPLICATES_CHECK;
+        /// Whether the device can run continuously
+        const ALWAYS_ON = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_ALWAYS_ON;
+        /// Supports updating an existing print record using new scans
+        const UPDATE_PRINT = libfprint_sys::FpDeviceFeature_FP_DEVICE_FEATURE_UPDATE_PRINT;
+    }
+}
+
+/// Writes the human readable names of the set flags, or `empty` if none is set.
+fn write_flags<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    names: impl Iterator<Item = &'a str>,
+    empty: &str,
+) -> std::fmt::Result {
+    let mut first = true;
+    for name in names {
+        if !first {
+            write!(f, ", ")?;
+        }
+        first = false;
+        // Turn `STORAGE_LIST` into `Storage list`
+        let mut chars = name.chars();
+        if let Some(c) = chars.next() {
+            write!(f, "{}", c)?;
         }
+        write!(f, "{}", chars.as_str().to_lowercase().replace('_', " "))?;
+    }
+    if first {
+        write!(f, "{}", empty)?;
+    }
+    Ok(())
+}
+
+impl Display for FpFingerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_flags(f, self.iter_names().map(|(name, _)| name), "None")
+    }
+}
+
+impl Display for FpDeviceFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_flags(f, self.iter_names().map(|(name, _)| name), "None")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FpDeviceFeature, FpFingerStatus};
+
+    #[test]
+    fn finger_status_combinations() {
+        let status = FpFingerStatus::from_bits_retain(
+            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED
+                | libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT,
+        );
+        assert!(status.contains(FpFingerStatus::NEEDED));
+        assert!(status.contains(FpFingerStatus::PRESENT));
+        assert_eq!(status.to_string(), "Needed, Present");
+        assert_eq!(FpFingerStatus::empty().to_string(), "None");
+    }
+
+    #[test]
+    fn feature_display() {
+        let features = FpDeviceFeature::VERIFY | FpDeviceFeature::STORAGE_LIST;
+        assert_eq!(features.iter().count(), 2);
+        assert_eq!(features.to_string(), "Verify, Storage list");
     }
 }
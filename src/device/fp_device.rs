@@ -1,8 +1,19 @@
-use glib::translate::{FromGlibPtrFull, FromGlibPtrNone, ToGlibPtr};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_channel::mpsc::{UnboundedReceiver, unbounded};
+use futures_core::Stream;
+use glib::{
+    SignalHandlerId,
+    object::ObjectExt,
+    translate::{FromGlibPtrNone, ToGlibPtr},
+};
 
 use super::{
     FpDevice,
-    enums::{FpDeviceFeature, FpFingerStatus, FpScanType},
+    enums::{FpDeviceFeature, FpFingerStatus, FpScanType, FpTemperature},
 };
 
 impl FpDevice {
@@ -24,7 +35,7 @@ impl FpDevice {
     pub fn name(&self) -> String {
         unsafe {
             let name = libfprint_sys::fp_device_get_name(self.to_glib_none().0);
-            glib::GString::from_glib_full(name).to_string()
+            glib::GString::from_glib_none(name).to_string()
         }
     }
     /// Retrieves the scan type of the device.
@@ -36,6 +47,18 @@ impl FpDevice {
             _ => panic!("Unknown scan type"),
         }
     }
+    /// Retrieves the current temperature of the device. Values unknown to this crate are reported as
+    /// `FpTemperature::Unknown`.
+    pub fn temperature(&self) -> FpTemperature {
+        let temperature =
+            unsafe { libfprint_sys::fp_device_get_temperature(self.to_glib_none().0) };
+        match temperature {
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_COLD => FpTemperature::Cold,
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_WARM => FpTemperature::Warm,
+            libfprint_sys::FpTemperature_FP_TEMPERATURE_HOT => FpTemperature::Hot,
+            other => FpTemperature::Unknown(other),
+        }
+    }
     /// Retrieves the number of enroll stages for this device.
     pub fn nr_enroll_stage(&self) -> i32 {
         unsafe { libfprint_sys::fp_device_get_nr_enroll_stages(self.to_glib_none().0) }
@@ -43,42 +66,214 @@ impl FpDevice {
     /// Retrieves the finger status flags for the device. This can be used by the UI to present the relevant feedback, although it is not guaranteed to be a relevant value when not performing any action.
     pub fn finger_status(&self) -> FpFingerStatus {
         let status = unsafe { libfprint_sys::fp_device_get_finger_status(self.to_glib_none().0) };
-        match status {
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NONE => FpFingerStatus::None,
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_NEEDED => FpFingerStatus::Needed,
-            libfprint_sys::FpFingerStatusFlags_FP_FINGER_STATUS_PRESENT => FpFingerStatus::Present,
-            _ => panic!("Unknown finger status"),
-        }
+        FpFingerStatus::from_bits_retain(status)
     }
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    /// Gets the FpDeviceFeature's supported by the device .
-    pub fn features(&self) -> Vec<FpDeviceFeature> {
-        // Unmask the features bitfield and return a vector of FpDeviceFeature
-        let mut features = Vec::new();
-        let x = unsafe { libfprint_sys::fp_device_get_features(self.to_glib_none().0) };
-        if x == 0 {
-            return vec![FpDeviceFeature::None];
-        } else {
-            (0..31).for_each(|i| {
-                let mask = 1 << i;
-                if (mask & x) > 0
-                    && let Ok(feature) = FpDeviceFeature::try_from(2_u32.pow(i))
-                {
-                    features.push(feature);
-                }
-            });
-        }
-        features
+    /// Gets the set of features supported by the device.
+    pub fn features(&self) -> FpDeviceFeature {
+        let features = unsafe { libfprint_sys::fp_device_get_features(self.to_glib_none().0) };
+        FpDeviceFeature::from_bits_retain(features)
     }
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    /// Checks if device supports the requested FpDeviceFeature.
+    /// Checks if device supports all the requested features.
     pub fn has_feature(&self, feature: FpDeviceFeature) -> bool {
         let res =
-            unsafe { libfprint_sys::fp_device_has_feature(self.to_glib_none().0, feature as u32) };
+            unsafe { libfprint_sys::fp_device_has_feature(self.to_glib_none().0, feature.bits()) };
         res == glib::ffi::GTRUE
     }
     /// Whether the device is open or not
     pub fn is_open(&self) -> bool {
         unsafe { libfprint_sys::fp_device_is_open(self.to_glib_none().0) == glib::ffi::GTRUE }
     }
+    /// Whether the device has been removed from the system. A removed device can no longer be used.
+    pub fn is_removed(&self) -> bool {
+        self.property("removed")
+    }
+}
+
+/// Notify-capable properties of a `FpDevice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceProperty {
+    /// `finger-status`, see `FpDevice::finger_status`.
+    FingerStatus,
+    /// `temperature`, see `FpDevice::temperature`.
+    Temperature,
+    /// `open`, see `FpDevice::is_open`.
+    Open,
+    /// `removed`, see `FpDevice::is_removed`.
+    Removed,
+    /// `nr-enroll-stages`, see `FpDevice::nr_enroll_stage`.
+    NrEnrollStages,
+    /// `scan-type`, see `FpDevice::scan_type`.
+    ScanType,
+    /// `driver`, see `FpDevice::driver`.
+    Driver,
+    /// `device-id`, see `FpDevice::device_id`.
+    DeviceId,
+    /// `name`, see `FpDevice::name`.
+    Name,
+}
+
+impl DeviceProperty {
+    /// The GObject property name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceProperty::FingerStatus => "finger-status",
+            DeviceProperty::Temperature => "temperature",
+            DeviceProperty::Open => "open",
+            DeviceProperty::Removed => "removed",
+            DeviceProperty::NrEnrollStages => "nr-enroll-stages",
+            DeviceProperty::ScanType => "scan-type",
+            DeviceProperty::Driver => "driver",
+            DeviceProperty::DeviceId => "device-id",
+            DeviceProperty::Name => "name",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let property = match name {
+            "finger-status" => DeviceProperty::FingerStatus,
+            "temperature" => DeviceProperty::Temperature,
+            "open" => DeviceProperty::Open,
+            "removed" => DeviceProperty::Removed,
+            "nr-enroll-stages" => DeviceProperty::NrEnrollStages,
+            "scan-type" => DeviceProperty::ScanType,
+            "driver" => DeviceProperty::Driver,
+            "device-id" => DeviceProperty::DeviceId,
+            "name" => DeviceProperty::Name,
+            _ => return None,
+        };
+        Some(property)
+    }
+}
+
+impl FpDevice {
+    /// Connect to the change notifications of `property`.
+    ///
+    /// Notifications are emitted while the thread default `glib::MainContext` is being iterated, which is the case
+    /// during any sync operation.
+    pub fn connect_property_notify<F: Fn(&FpDevice) + 'static>(
+        &self,
+        property: DeviceProperty,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_notify_local(Some(property.name()), move |device, _| f(device))
+    }
+    #[cfg(not(doctest))]
+    /// Connect to the changes of the finger status, i.e to prompt the user to place their finger.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, FpFingerStatus};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// dev.connect_finger_status_notify(|dev| {
+    ///     if dev.finger_status().contains(FpFingerStatus::NEEDED) {
+    ///         println!("Place your finger on the sensor");
+    ///     }
+    /// });
+    /// ```
+    pub fn connect_finger_status_notify<F: Fn(&FpDevice) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::FingerStatus, f)
+    }
+    /// Connect to the changes of the device temperature, see `FpDevice::temperature`.
+    pub fn connect_temperature_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::Temperature, f)
+    }
+    /// Connect to the device being opened or closed.
+    pub fn connect_open_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::Open, f)
+    }
+    /// Connect to the device being removed from the system.
+    pub fn connect_removed_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::Removed, f)
+    }
+    /// Connect to the changes of the number of enroll stages.
+    pub fn connect_nr_enroll_stages_notify<F: Fn(&FpDevice) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::NrEnrollStages, f)
+    }
+    /// Connect to the changes of the scan type.
+    pub fn connect_scan_type_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::ScanType, f)
+    }
+    /// Connect to the changes of the driver ID.
+    pub fn connect_driver_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::Driver, f)
+    }
+    /// Connect to the changes of the device ID.
+    pub fn connect_device_id_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::DeviceId, f)
+    }
+    /// Connect to the changes of the device name.
+    pub fn connect_name_notify<F: Fn(&FpDevice) + 'static>(&self, f: F) -> SignalHandlerId {
+        self.connect_property_notify(DeviceProperty::Name, f)
+    }
+
+    #[cfg(not(doctest))]
+    /// Returns a stream of the property changes of the device.
+    ///
+    /// The stream is driven by the thread default `glib::MainContext`, the signal handler is disconnected when it is
+    /// dropped.
+    /// # Example:
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// use libfprint_rs::{DeviceProperty, FpContext, FpFingerStatus};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// let mut changes = dev.watch();
+    /// glib::MainContext::default().spawn_local(async move {
+    ///     while let Some(property) = changes.next().await {
+    ///         if property == DeviceProperty::FingerStatus {
+    ///             println!("Finger status: {:?}", dev.finger_status());
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn watch(&self) -> DeviceWatch {
+        let (sender, receiver) = unbounded();
+        let handler = self.connect_notify_local(None, move |_, pspec| {
+            if let Some(property) = DeviceProperty::from_name(pspec.name()) {
+                // The stream might be gone already, in which case nobody is interested in the change
+                let _ = sender.unbounded_send(property);
+            }
+        });
+
+        DeviceWatch {
+            device: self.clone(),
+            receiver,
+            handler: Some(handler),
+        }
+    }
+}
+
+/// Stream of `DeviceProperty` changes, created by `FpDevice::watch`.
+pub struct DeviceWatch {
+    device: FpDevice,
+    receiver: UnboundedReceiver<DeviceProperty>,
+    handler: Option<SignalHandlerId>,
+}
+
+impl Stream for DeviceWatch {
+    type Item = DeviceProperty;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl Drop for DeviceWatch {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            self.device.disconnect(handler);
+        }
+    }
 }
@@ -0,0 +1,189 @@
+use gio::Cancellable;
+
+use crate::{FpError, print::FpPrint};
+
+use super::{FpDevice, FpMatchCb, device_async::FpFuture};
+
+/// Enrolled prints keyed by an identifier of the caller, such as a user id, to identify against with
+/// `FpDevice::identify_in_sync` or `FpDevice::identify_in`.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FileStore, Gallery, PrintStore};
+///
+/// // Keyed by username
+/// let gallery: Gallery<String> = FileStore::system().load_all(&dev).unwrap().into_iter().collect();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Gallery<K> {
+    entries: Vec<(K, FpPrint)>,
+}
+
+impl<K> Gallery<K> {
+    /// Creates an empty gallery.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds `print` to the gallery under `key`.
+    pub fn push(&mut self, key: K, print: FpPrint) {
+        self.entries.push((key, print));
+    }
+
+    /// Number of prints in the gallery.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the gallery holds no print.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the keys and prints of the gallery.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &FpPrint)> {
+        self.entries.iter().map(|(key, print)| (key, print))
+    }
+}
+
+impl<K: Clone> Gallery<K> {
+    /// Splits the gallery into the entries compatible with `device`, and the number of the other ones.
+    fn compatible(&self, device: &FpDevice) -> (Vec<(K, FpPrint)>, usize) {
+        let compatible: Vec<(K, FpPrint)> = self
+            .entries
+            .iter()
+            .filter(|(_, print)| print.compatible(device))
+            .cloned()
+            .collect();
+        let skipped = self.entries.len() - compatible.len();
+        (compatible, skipped)
+    }
+}
+
+impl<K> Default for Gallery<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> FromIterator<(K, FpPrint)> for Gallery<K> {
+    fn from_iter<I: IntoIterator<Item = (K, FpPrint)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<K> Extend<(K, FpPrint)> for Gallery<K> {
+    fn extend<I: IntoIterator<Item = (K, FpPrint)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+    }
+}
+
+impl<K> IntoIterator for Gallery<K> {
+    type Item = (K, FpPrint);
+    type IntoIter = std::vec::IntoIter<(K, FpPrint)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// Result of `FpDevice::identify_in_sync` and `FpDevice::identify_in`.
+#[derive(Debug, Clone)]
+pub struct GalleryMatch<K> {
+    /// Key of the matching print, `None` if no print matched.
+    pub key: Option<K>,
+    /// The newly scanned print, if the driver provides one.
+    pub scanned: Option<FpPrint>,
+    /// Number of prints of the gallery left out because they are not compatible with the device, i.e enrolled on
+    /// another reader.
+    pub skipped: usize,
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Identify a print synchronously among the prints of `gallery`, resolving to the key of the matching one.
+    ///
+    /// Prints that are not compatible with the device are left out and counted in `GalleryMatch::skipped`. Returns
+    /// `FpError::DataNotFound` without scanning if no print of the gallery is compatible.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{FpContext, Gallery};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    /// dev.open_sync(None).unwrap();
+    ///
+    /// let gallery: Gallery<u32> = prints_by_user_id().into_iter().collect();
+    /// let res = dev.identify_in_sync(&gallery, None).unwrap();
+    /// if res.skipped > 0 {
+    ///     println!("{} prints were enrolled on another reader", res.skipped);
+    /// }
+    /// match res.key {
+    ///     Some(uid) => println!("Identified user {uid}"),
+    ///     None => println!("No match"),
+    /// }
+    /// ```
+    pub fn identify_in_sync<K: Clone>(
+        &self,
+        gallery: &Gallery<K>,
+        cancellable: Option<&Cancellable>,
+    ) -> Result<GalleryMatch<K>, FpError> {
+        let (entries, skipped) = gallery.compatible(self);
+        let prints = check_compatible(&entries, skipped)?;
+
+        let (matched, scanned) =
+            self.identify_scanned_sync(&prints, cancellable, None::<FpMatchCb<()>>, None)?;
+        Ok(GalleryMatch {
+            key: key_of(entries, matched),
+            scanned,
+            skipped,
+        })
+    }
+
+    /// Identify a print asynchronously among the prints of `gallery`, resolving to the key of the matching one.
+    ///
+    /// See `FpDevice::identify_in_sync`.
+    pub fn identify_in<K: Clone + 'static>(
+        &self,
+        gallery: &Gallery<K>,
+    ) -> FpFuture<GalleryMatch<K>> {
+        let device = self.clone();
+        let (entries, skipped) = gallery.compatible(self);
+        Box::pin(async move {
+            let prints = check_compatible(&entries, skipped)?;
+            let (matched, scanned) = device
+                .identify(&prints, None::<FpMatchCb<()>>, None)
+                .await?;
+            Ok(GalleryMatch {
+                key: key_of(entries, matched),
+                scanned,
+                skipped,
+            })
+        })
+    }
+}
+
+/// The prints of the compatible `entries`, or an error if there are none.
+fn check_compatible<K>(entries: &[(K, FpPrint)], skipped: usize) -> Result<Vec<FpPrint>, FpError> {
+    if entries.is_empty() {
+        return Err(FpError::DataNotFound(format!(
+            "No print of the gallery is compatible with the device, {skipped} skipped"
+        )));
+    }
+    Ok(entries.iter().map(|(_, print)| print.clone()).collect())
+}
+
+/// Key of the entry holding `matched`.
+fn key_of<K>(entries: Vec<(K, FpPrint)>, matched: Option<FpPrint>) -> Option<K> {
+    let matched = matched?;
+    // libfprint returns the matching object of the gallery, fall back to comparing the prints in case a driver does not
+    let position = entries
+        .iter()
+        .position(|(_, print)| *print == matched)
+        .or_else(|| entries.iter().position(|(_, print)| print.equal(&matched)))?;
+    entries.into_iter().nth(position).map(|(key, _)| key)
+}
@@ -1,10 +1,22 @@
 mod callback;
+mod device_async;
 mod device_sync;
+mod enroll_checked;
+mod enroll_stream;
 mod enums;
 mod fp_device;
+mod gallery;
+mod thermal;
 mod user_data;
 
+pub use device_async::FpFuture;
+pub(crate) use device_async::start_async;
 pub use device_sync::{FpEnrollProgress, FpMatchCb};
+pub use enroll_checked::EnrollOutcome;
+pub use enroll_stream::{EnrollEvent, EnrollStream};
+pub use enums::{FpDeviceFeature, FpFingerStatus, FpScanType, FpTemperature};
+pub use fp_device::{DeviceProperty, DeviceWatch};
+pub use gallery::{Gallery, GalleryMatch};
 use gio::AsyncInitable;
 use glib::wrapper;
 
@@ -0,0 +1,95 @@
+use std::{cell::RefCell, time::Duration};
+
+use futures_channel::oneshot;
+use glib::{SignalHandlerId, object::ObjectExt};
+
+use crate::FpError;
+
+use super::{FpDevice, device_async::FpFuture, enums::FpTemperature};
+
+/// Disconnects the temperature handler once the wait is over, or when the future is dropped.
+struct HandlerGuard {
+    device: FpDevice,
+    handler: Option<SignalHandlerId>,
+}
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            self.device.disconnect(handler);
+        }
+    }
+}
+
+impl FpDevice {
+    #[cfg(not(doctest))]
+    /// Wait asynchronously until the device is `FpTemperature::Cold` or `FpTemperature::Warm`.
+    ///
+    /// Resolves immediately if the device is cold or warm. Otherwise, including for `FpTemperature::Unknown` states,
+    /// waits for the temperature to drop, for at most `timeout` if given, in which case `FpError::TooHot` is returned
+    /// when the device did not cool down in time. `FpError::Cancelled` is returned if the device stops reporting its
+    /// temperature meanwhile. The future must be driven by the thread default `glib::MainContext`.
+    /// # Example:
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use libfprint_rs::{FpContext, FpMatchCb};
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open().await.unwrap();
+    ///     loop {
+    ///         dev.wait_for_cool_down(Some(Duration::from_secs(60))).await.unwrap();
+    ///         let (matched, _) = dev.verify(&enrolled_print, None::<FpMatchCb<()>>, None).await.unwrap();
+    ///         println!("Matched: {}", matched);
+    ///     }
+    /// });
+    /// ```
+    pub fn wait_for_cool_down(&self, timeout: Option<Duration>) -> FpFuture<()> {
+        let device = self.clone();
+        Box::pin(async move {
+            if is_cool(device.temperature()) {
+                return Ok(());
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            let sender = RefCell::new(Some(sender));
+            let handler = device.connect_temperature_notify(move |device| {
+                if is_cool(device.temperature())
+                    && let Some(sender) = sender.borrow_mut().take()
+                {
+                    let _ = sender.send(());
+                }
+            });
+            let _guard = HandlerGuard {
+                device: device.clone(),
+                handler: Some(handler),
+            };
+
+            let cooled = match timeout {
+                Some(timeout) => glib::future_with_timeout(timeout, receiver)
+                    .await
+                    .map_err(|_| FpError::TooHot("Device did not cool down in time".to_string()))?,
+                None => receiver.await,
+            };
+            // The sender is only dropped without sending if the handler went away, i.e with the device
+            cooled.map_err(|_| {
+                FpError::Cancelled("Stopped waiting for the device to cool down".to_string())
+            })
+        })
+    }
+
+    /// Block until the device is `FpTemperature::Cold` or `FpTemperature::Warm`, iterating the thread default `glib::MainContext`.
+    ///
+    /// See `FpDevice::wait_for_cool_down`.
+    pub fn wait_for_cool_down_sync(&self, timeout: Option<Duration>) -> Result<(), FpError> {
+        glib::MainContext::ref_thread_default().block_on(self.wait_for_cool_down(timeout))
+    }
+}
+
+/// Whether operations can run at `temperature`. States unknown to this crate are not assumed to be cool.
+fn is_cool(temperature: FpTemperature) -> bool {
+    matches!(temperature, FpTemperature::Cold | FpTemperature::Warm)
+}
@@ -19,13 +19,13 @@ where
 
 impl<F, T> UserData<F, T>
 where
-    F: Fn(&FpDevice, Option<FpPrint>, FpPrint, Option<glib::Error>, &Option<T>),
+    F: Fn(&FpDevice, Option<FpPrint>, Option<FpPrint>, Option<glib::Error>, &Option<T>),
 {
     pub(crate) fn callback_match(
         &self,
         device: &FpDevice,
         match_print: Option<FpPrint>,
-        print: FpPrint,
+        print: Option<FpPrint>,
         error: Option<glib::Error>,
     ) {
         (self.function)(device, match_print, print, error, &self.data);
@@ -0,0 +1,296 @@
+//! `Send` and `Sync` handle on a device, owned by a `MainLoopThread`.
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use futures_channel::mpsc::UnboundedSender;
+
+use crate::{
+    EnrollTemplate, FpDevice, FpDeviceFeature, FpError, FpFinger, FpMatchCb, FpPrint, FpScanType,
+    MainLoopThread,
+};
+
+/// Greyscale image captured through `DeviceHandle::capture`, see `FpImage::data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    /// One byte per pixel, row by row.
+    pub data: Vec<u8>,
+}
+
+#[cfg(not(doctest))]
+/// Thread safe handle on a `FpDevice`.
+///
+/// The device itself lives on the thread of a `MainLoopThread`, and each method sends it a request, resolving to its
+/// result. libfprint runs one operation at a time per device: while a request is in progress, other requests made
+/// through any handle on the same device fail right away with `FpError::Busy`, including the handles of another
+/// `DeviceHandle::list`.
+///
+/// Prints cross the thread boundary serialized, as returned by `FpPrint::serialize`.
+/// # Example:
+/// ```no_run
+/// use std::sync::Arc;
+/// use libfprint_rs::{DeviceHandle, FpFinger, MainLoopThread};
+///
+/// let main_loop = Arc::new(MainLoopThread::new().unwrap());
+/// let dev = DeviceHandle::list(&main_loop).await.remove(0);
+///
+/// dev.open().await?;
+/// let print = dev.enroll(FpFinger::RightIndex, "bruce".to_string(), None).await?;
+/// // Shared with other tasks and threads
+/// let matched = dev.clone().verify(print).await?;
+/// ```
+#[derive(Clone)]
+pub struct DeviceHandle {
+    main_loop: Arc<MainLoopThread>,
+    info: Arc<DeviceInfo>,
+}
+
+/// Properties of the device, read when the handle is created.
+#[derive(Debug)]
+struct DeviceInfo {
+    device_id: String,
+    driver: String,
+    name: String,
+    scan_type: FpScanType,
+    features: FpDeviceFeature,
+    nr_enroll_stage: i32,
+}
+
+/// Clears the busy flag of the device once the request is over, or dropped.
+struct BusyGuard(Arc<AtomicBool>);
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl DeviceHandle {
+    /// Handles on the devices of the `FpContext` of `main_loop`.
+    pub fn list(main_loop: &Arc<MainLoopThread>) -> impl Future<Output = Vec<Self>> + Send + use<> {
+        let main_loop = main_loop.clone();
+        let infos = main_loop.run(|ctx| {
+            let infos: Vec<DeviceInfo> = ctx
+                .devices()
+                .iter()
+                .map(|dev| DeviceInfo {
+                    device_id: dev.device_id(),
+                    driver: dev.driver(),
+                    name: dev.name(),
+                    scan_type: dev.scan_type(),
+                    features: dev.features(),
+                    nr_enroll_stage: dev.nr_enroll_stage(),
+                })
+                .collect();
+            std::future::ready(infos)
+        });
+        async move {
+            infos
+                .await
+                .into_iter()
+                .map(|info| Self {
+                    main_loop: main_loop.clone(),
+                    info: Arc::new(info),
+                })
+                .collect()
+        }
+    }
+
+    /// See `FpDevice::device_id`.
+    pub fn device_id(&self) -> &str {
+        &self.info.device_id
+    }
+
+    /// See `FpDevice::driver`.
+    pub fn driver(&self) -> &str {
+        &self.info.driver
+    }
+
+    /// See `FpDevice::name`.
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// See `FpDevice::scan_type`.
+    pub fn scan_type(&self) -> FpScanType {
+        self.info.scan_type
+    }
+
+    /// See `FpDevice::features`.
+    pub fn features(&self) -> FpDeviceFeature {
+        self.info.features
+    }
+
+    /// See `FpDevice::nr_enroll_stage`.
+    pub fn nr_enroll_stage(&self) -> i32 {
+        self.info.nr_enroll_stage
+    }
+
+    /// Whether a request is in progress.
+    pub fn is_busy(&self) -> bool {
+        self.main_loop
+            .busy_flag(&self.info.device_id)
+            .load(Ordering::Acquire)
+    }
+
+    /// Runs `f` with the device on the main loop thread, unless another request is in progress.
+    fn request<F, Fut, R>(
+        &self,
+        f: F,
+    ) -> impl Future<Output = Result<R, FpError>> + Send + use<F, Fut, R>
+    where
+        F: FnOnce(FpDevice) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<R, FpError>> + 'static,
+        R: Send + 'static,
+    {
+        let busy = self.main_loop.busy_flag(&self.info.device_id);
+        let fut = (!busy.swap(true, Ordering::AcqRel)).then(|| {
+            let guard = BusyGuard(busy);
+            let device_id = self.info.device_id.clone();
+            self.main_loop.run(move |ctx| {
+                let dev = ctx
+                    .devices()
+                    .into_iter()
+                    .find(|dev| dev.device_id() == device_id);
+                async move {
+                    let _guard = guard;
+                    match dev {
+                        Some(dev) => f(dev).await,
+                        None => Err(FpError::Removed(
+                            "Device is no longer available".to_string(),
+                        )),
+                    }
+                }
+            })
+        });
+        async move {
+            match fut {
+                Some(fut) => fut.await,
+                None => Err(FpError::Busy(
+                    "Device is busy with another request".to_string(),
+                )),
+            }
+        }
+    }
+
+    /// See `FpDevice::open`.
+    pub fn open(&self) -> impl Future<Output = Result<(), FpError>> + Send + use<> {
+        self.request(|dev| dev.open())
+    }
+
+    /// See `FpDevice::close`.
+    pub fn close(&self) -> impl Future<Output = Result<(), FpError>> + Send + use<> {
+        self.request(|dev| dev.close())
+    }
+
+    /// Enrolls `finger` for `username`, resolving to the serialized print. The template is made with
+    /// `EnrollTemplate::builder`, whose errors are returned by the future.
+    ///
+    /// When `progress` is given, it receives the number of completed stages after each stage, or the retry error of a
+    /// failed stage.
+    pub fn enroll(
+        &self,
+        finger: FpFinger,
+        username: String,
+        progress: Option<UnboundedSender<Result<i32, FpError>>>,
+    ) -> impl Future<Output = Result<Vec<u8>, FpError>> + Send + use<> {
+        self.request(move |dev| {
+            let template = EnrollTemplate::builder(&dev)
+                .finger(finger)
+                .username(&username)
+                .build();
+            let progress = progress.map(|sender| {
+                move |_: &FpDevice,
+                      stage: i32,
+                      _: Option<FpPrint>,
+                      error: Option<glib::Error>,
+                      _: &Option<()>| {
+                    let event = match error {
+                        Some(error) => Err(error.into()),
+                        None => Ok(stage),
+                    };
+                    let _ = sender.unbounded_send(event);
+                }
+            });
+            let enroll = template.map(|template| dev.enroll(template, progress, None));
+            async move { Ok(enroll?.await?.serialize()?) }
+        })
+    }
+
+    /// Verifies the finger against the serialized `print`.
+    pub fn verify(
+        &self,
+        print: Vec<u8>,
+    ) -> impl Future<Output = Result<bool, FpError>> + Send + use<> {
+        self.request(move |dev| async move {
+            let print = FpPrint::deserialize(&print)?;
+            let (matched, _) = dev.verify(&print, None::<FpMatchCb<()>>, None).await?;
+            Ok(matched)
+        })
+    }
+
+    /// Identifies the finger among the serialized prints of `gallery`, resolving to the index of the matching one.
+    pub fn identify(
+        &self,
+        gallery: Vec<Vec<u8>>,
+    ) -> impl Future<Output = Result<Option<usize>, FpError>> + Send + use<> {
+        self.request(move |dev| async move {
+            let prints = gallery
+                .iter()
+                .map(|data| FpPrint::deserialize(data))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (matched, _) = dev.identify(&prints, None::<FpMatchCb<()>>, None).await?;
+            Ok(matched.and_then(|matched| prints.iter().position(|print| print.equal(&matched))))
+        })
+    }
+
+    /// See `FpDevice::capture`.
+    pub fn capture(
+        &self,
+        wait_for_finger: bool,
+    ) -> impl Future<Output = Result<ImageData, FpError>> + Send + use<> {
+        self.request(move |dev| async move {
+            let image = dev.capture(wait_for_finger).await?;
+            Ok(ImageData {
+                width: image.width(),
+                height: image.height(),
+                data: image.data(),
+            })
+        })
+    }
+
+    /// Lists the prints stored on the device, serialized.
+    pub fn list_prints(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Vec<u8>>, FpError>> + Send + use<> {
+        self.request(|dev| async move {
+            let prints = dev.list_prints().await?;
+            Ok(prints
+                .iter()
+                .map(FpPrint::serialize)
+                .collect::<Result<_, _>>()?)
+        })
+    }
+
+    /// Deletes the serialized `print` from the device storage.
+    pub fn delete_print(
+        &self,
+        print: Vec<u8>,
+    ) -> impl Future<Output = Result<(), FpError>> + Send + use<> {
+        self.request(move |dev| async move {
+            let print = FpPrint::deserialize(&print)?;
+            dev.delete_print(&print).await
+        })
+    }
+
+    /// See `FpDevice::clear_storage`.
+    pub fn clear_storage(&self) -> impl Future<Output = Result<(), FpError>> + Send + use<> {
+        self.request(|dev| dev.clear_storage())
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt::Display;
+
+use glib::translate::FromGlib;
+
+/// Errors returned by the device operations.
+///
+/// Errors from libfprint's `FpDeviceError` and `FpDeviceRetry` domains are mapped to their own variant, each one holding
+/// the message reported by the driver. Errors from any other GLib domain are kept as they are in `FpError::Other`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FpError {
+    /// A general error occurred.
+    General(String),
+    /// The device does not support the requested operation.
+    NotSupported(String),
+    /// The device needs to be opened to start this operation.
+    NotOpen(String),
+    /// The device has already been opened.
+    AlreadyOpen(String),
+    /// The device is busy with another request.
+    Busy(String),
+    /// Protocol error.
+    Proto(String),
+    /// The passed data is invalid.
+    DataInvalid(String),
+    /// Requested print was not found on device.
+    DataNotFound(String),
+    /// No space on device available for operation.
+    DataFull(String),
+    /// Enrolling template duplicates storaged templates.
+    DataDuplicate(String),
+    /// The device has been removed.
+    Removed(String),
+    /// The device might be getting too hot.
+    TooHot(String),
+    /// The scan did not succeed due to poor scan quality or other general user scanning problem.
+    RetryGeneral(String),
+    /// The scan did not succeed because the finger swipe was too short.
+    RetryTooShort(String),
+    /// The scan did not succeed because the finger was not centered on the scanner.
+    RetryCenterFinger(String),
+    /// The scan did not succeed due to quality or pressure problems; the user should remove their finger from the scanner
+    /// before retrying.
+    RetryRemoveFinger(String),
+    /// The operation was cancelled.
+    Cancelled(String),
+    /// An error from an unknown code or from a different GLib domain.
+    Other(glib::Error),
+}
+
+/// Reason why a scan has to be retried.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FpRetryReason {
+    /// Poor scan quality or other general user scanning problem.
+    General,
+    /// The finger swipe was too short.
+    TooShort,
+    /// The finger was not centered on the scanner.
+    CenterFinger,
+    /// The user should remove their finger from the scanner before retrying.
+    RemoveFinger,
+}
+
+impl Display for FpRetryReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpRetryReason::General => write!(f, "Please try again"),
+            FpRetryReason::TooShort => write!(f, "Swipe was too short, please try again"),
+            FpRetryReason::CenterFinger => write!(f, "Finger not centered, please try again"),
+            FpRetryReason::RemoveFinger => write!(f, "Remove your finger and try again"),
+        }
+    }
+}
+
+impl FpRetryReason {
+    /// Returns the `FpDeviceRetry` code of the reason.
+    #[cfg(feature = "testing")]
+    pub(crate) fn code(&self) -> u32 {
+        match self {
+            FpRetryReason::General => libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_GENERAL,
+            FpRetryReason::TooShort => libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_SHORT,
+            FpRetryReason::CenterFinger => {
+                libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_CENTER_FINGER
+            }
+            FpRetryReason::RemoveFinger => {
+                libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_REMOVE_FINGER
+            }
+        }
+    }
+}
+
+impl FpError {
+    /// Whether the error is a retry error, in which case the user should simply scan their finger again.
+    pub fn is_retry(&self) -> bool {
+        self.retry_reason().is_some()
+    }
+    /// Returns the reason of a retry error, or `None` if the error is not a retry error.
+    pub fn retry_reason(&self) -> Option<FpRetryReason> {
+        match self {
+            FpError::RetryGeneral(_) => Some(FpRetryReason::General),
+            FpError::RetryTooShort(_) => Some(FpRetryReason::TooShort),
+            FpError::RetryCenterFinger(_) => Some(FpRetryReason::CenterFinger),
+            FpError::RetryRemoveFinger(_) => Some(FpRetryReason::RemoveFinger),
+            _ => None,
+        }
+    }
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        match self {
+            FpError::General(msg)
+            | FpError::NotSupported(msg)
+            | FpError::NotOpen(msg)
+            | FpError::AlreadyOpen(msg)
+            | FpError::Busy(msg)
+            | FpError::Proto(msg)
+            | FpError::DataInvalid(msg)
+            | FpError::DataNotFound(msg)
+            | FpError::DataFull(msg)
+            | FpError::DataDuplicate(msg)
+            | FpError::Removed(msg)
+            | FpError::TooHot(msg)
+            | FpError::RetryGeneral(msg)
+            | FpError::RetryTooShort(msg)
+            | FpError::RetryCenterFinger(msg)
+            | FpError::RetryRemoveFinger(msg)
+            | FpError::Cancelled(msg) => msg,
+            FpError::Other(error) => error.message(),
+        }
+    }
+
+    /// Returns the `FpDeviceError` code matching the error, `None` if it is not a device error.
+    #[cfg(feature = "testing")]
+    pub(crate) fn device_error_code(&self) -> Option<u32> {
+        let code = match self {
+            FpError::General(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_GENERAL,
+            FpError::NotSupported(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_NOT_SUPPORTED,
+            FpError::NotOpen(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_NOT_OPEN,
+            FpError::AlreadyOpen(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_ALREADY_OPEN,
+            FpError::Busy(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_BUSY,
+            FpError::Proto(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_PROTO,
+            FpError::DataInvalid(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_INVALID,
+            FpError::DataNotFound(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_NOT_FOUND,
+            FpError::DataFull(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_FULL,
+            FpError::DataDuplicate(_) => {
+                libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_DUPLICATE
+            }
+            FpError::Removed(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_REMOVED,
+            FpError::TooHot(_) => libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_TOO_HOT,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    fn from_device_error(code: u32, message: String) -> Option<Self> {
+        let error = match code {
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_GENERAL => FpError::General(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_NOT_SUPPORTED => {
+                FpError::NotSupported(message)
+            }
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_NOT_OPEN => FpError::NotOpen(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_ALREADY_OPEN => {
+                FpError::AlreadyOpen(message)
+            }
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_BUSY => FpError::Busy(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_PROTO => FpError::Proto(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_INVALID => {
+                FpError::DataInvalid(message)
+            }
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_NOT_FOUND => {
+                FpError::DataNotFound(message)
+            }
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_FULL => FpError::DataFull(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_DUPLICATE => {
+                FpError::DataDuplicate(message)
+            }
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_REMOVED => FpError::Removed(message),
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_TOO_HOT => FpError::TooHot(message),
+            _ => return None,
+        };
+        Some(error)
+    }
+
+    fn from_retry_error(code: u32, message: String) -> Option<Self> {
+        let error = match code {
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_GENERAL => FpError::RetryGeneral(message),
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_SHORT => {
+                FpError::RetryTooShort(message)
+            }
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_CENTER_FINGER => {
+                FpError::RetryCenterFinger(message)
+            }
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_REMOVE_FINGER => {
+                FpError::RetryRemoveFinger(message)
+            }
+            _ => return None,
+        };
+        Some(error)
+    }
+}
+
+impl Display for FpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for FpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FpError::Other(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<glib::Error> for FpError {
+    fn from(error: glib::Error) -> Self {
+        let domain = error.domain();
+        let message = error.message().to_string();
+        let mapped = if error.matches(gio::IOErrorEnum::Cancelled) {
+            Some(FpError::Cancelled(message))
+        } else if domain
+            == unsafe { glib::Quark::from_glib(libfprint_sys::fp_device_error_quark()) }
+        {
+            u32::try_from(error.code())
+                .ok()
+                .and_then(|code| FpError::from_device_error(code, message))
+        } else if domain
+            == unsafe { glib::Quark::from_glib(libfprint_sys::fp_device_retry_quark()) }
+        {
+            u32::try_from(error.code())
+                .ok()
+                .and_then(|code| FpError::from_retry_error(code, message))
+        } else {
+            None
+        };
+        mapped.unwrap_or(FpError::Other(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::translate::FromGlib;
+
+    use super::{FpError, FpRetryReason};
+
+    #[test]
+    fn map_device_domains() {
+        let retry = unsafe { glib::Quark::from_glib(libfprint_sys::fp_device_retry_quark()) };
+        let error = glib::Error::with_domain(
+            retry,
+            libfprint_sys::FpDeviceRetry_FP_DEVICE_RETRY_TOO_SHORT as i32,
+            "Too short",
+        );
+        let error = FpError::from(error);
+        assert_eq!(error, FpError::RetryTooShort("Too short".into()));
+        assert!(error.is_retry());
+        assert_eq!(error.retry_reason(), Some(FpRetryReason::TooShort));
+
+        let device = unsafe { glib::Quark::from_glib(libfprint_sys::fp_device_error_quark()) };
+        let error = glib::Error::with_domain(
+            device,
+            libfprint_sys::FpDeviceError_FP_DEVICE_ERROR_DATA_NOT_FOUND as i32,
+            "Not found",
+        );
+        let error = FpError::from(error);
+        assert_eq!(error, FpError::DataNotFound("Not found".into()));
+        assert!(!error.is_retry());
+    }
+
+    #[test]
+    fn map_other_domains() {
+        let error = glib::Error::new(gio::IOErrorEnum::Cancelled, "Operation was cancelled");
+        assert_eq!(
+            FpError::from(error),
+            FpError::Cancelled("Operation was cancelled".into())
+        );
+
+        let error = glib::Error::new(glib::FileError::Noent, "No such file");
+        assert!(matches!(FpError::from(error), FpError::Other(_)));
+    }
+}
@@ -3,6 +3,11 @@ use std::fmt::Display;
 /// Enum representing a finger. This can be used to specify which finger was used to enroll a new print and can be added as part of the
 /// print metadata.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum FpFinger {
     Unknown = libfprint_sys::FpFinger_FP_FINGER_UNKNOWN as isize,
     LeftThumb = libfprint_sys::FpFinger_FP_FINGER_LEFT_THUMB as isize,
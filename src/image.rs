@@ -1,9 +1,16 @@
 // All methods are declared
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use gio::Cancellable;
 use glib::{
+    object::{Cast, ObjectClassExt, ObjectType},
+    prelude::{StaticType, ToValue},
     translate::{FromGlibContainer, FromGlibPtrFull, ToGlibPtr},
     wrapper,
 };
 
+use crate::{FpError, FpFuture, device::start_async};
+
 wrapper! {
 #[cfg(not(doctest))]
 /// Struct representing an image of a fingerprint. Not all devices support this feature.
@@ -11,7 +18,6 @@ wrapper! {
 /// ```rust
 /// use libfprint_rs::FpContext;
 /// use std::fs::File;
-/// use std::io::Write;
 ///
 /// let context = FpContext::new();
 /// let devices = context.devices();
@@ -19,12 +25,9 @@ wrapper! {
 ///
 /// device.open_sync(None).unwrap();
 /// let image = device.capture_sync(true, None).unwrap();
-/// let data = image.data();
 ///
-/// let mut file = File::create("image.pgm").unwrap();
-/// let header = format!("P5\n{} {}\n255\n", image.width(), image.height());
-/// file.write_all(header.as_bytes()).unwrap();
-/// file.write_all(data.as_slice()).unwrap();
+/// let file = File::create("image.pgm").unwrap();
+/// image.write_pgm(file).unwrap();
 /// ```
     pub struct FpImage(Object<libfprint_sys::FpImage, libfprint_sys::FpImageClass>);
 
@@ -38,6 +41,71 @@ impl FpImage {
         unsafe { FpImage::from_glib_full(libfprint_sys::fp_image_new(width as i32, height as i32)) }
     }
 
+    /// Creates an image with the resolution `ppmm`, in points per millimeter, from greyscale pixels, one byte per
+    /// pixel, row by row. This allows running `FpImage::detect_minutiae` on stored captures, with the `FpImage::ppmm`
+    /// they were captured at.
+    ///
+    /// The resolution is set through the `ppmm` construct property of `FpImage`. Returns `FpError::NotSupported` if
+    /// the libfprint in use does not provide it.
+    pub fn from_grayscale(
+        width: u32,
+        height: u32,
+        ppmm: f64,
+        data: &[u8],
+    ) -> Result<Self, FpError> {
+        let expected = width as usize * height as usize;
+        if data.len() != expected {
+            return Err(FpError::DataInvalid(format!(
+                "Expected {} bytes of image data, got {}",
+                expected,
+                data.len()
+            )));
+        }
+
+        let image = Self::with_properties([
+            ("width", width.to_value()),
+            ("height", height.to_value()),
+            ("ppmm", ppmm.to_value()),
+        ])?;
+        unsafe {
+            let mut len = 0;
+            let buffer = libfprint_sys::fp_image_get_data(image.to_glib_none().0, &mut len);
+            assert_eq!(len as usize, expected);
+            // SAFETY: `fp_image_get_data` returns the pixel buffer of `len` bytes that the image allocated when it was
+            // constructed and owns for its whole lifetime. It is only const in the API because images are read-only
+            // once captured. The image was just created and is not shared with libfprint or another thread yet, and
+            // no Rust reference to the buffer exists, so writing to it can not race nor alias.
+            std::ptr::copy_nonoverlapping(data.as_ptr(), buffer.cast_mut(), expected);
+        }
+        Ok(image)
+    }
+
+    /// Constructs an image with the given construct properties, converted to the type libfprint declares for them.
+    fn with_properties<const N: usize>(
+        properties: [(&str, glib::Value); N],
+    ) -> Result<Self, FpError> {
+        let class = glib::Class::<glib::Object>::from_type(Self::static_type())
+            .expect("FpImage is a GObject class");
+        let mut builder = glib::Object::builder_with_type(Self::static_type());
+        for (name, value) in properties {
+            let value = class
+                .find_property(name)
+                .filter(|pspec| pspec.flags().contains(glib::ParamFlags::WRITABLE))
+                .and_then(|pspec| value.transform_with_type(pspec.value_type()).ok())
+                .ok_or_else(|| {
+                    FpError::NotSupported(format!(
+                        "libfprint does not allow setting the {} of an image",
+                        name
+                    ))
+                })?;
+            builder = builder.property(name, value);
+        }
+        Ok(builder
+            .build()
+            .downcast()
+            .expect("the object is built with the FpImage type"))
+    }
+
     /// Gets the pixel width of an image.
     pub fn width(&self) -> u32 {
         unsafe { libfprint_sys::fp_image_get_width(self.to_glib_none().0) as u32 }
@@ -53,14 +121,96 @@ impl FpImage {
         unsafe { libfprint_sys::fp_image_get_ppmm(self.to_glib_none().0) }
     }
 
-    fn _minutiae(&self) {
-        unimplemented!()
+    /// Gets the minutiae detected in the image. Empty until `FpImage::detect_minutiae` completed.
+    pub fn minutiae(&self) -> Vec<FpMinutia> {
+        unsafe {
+            let array = libfprint_sys::fp_image_get_minutiae(self.to_glib_none().0);
+            if array.is_null() {
+                return Vec::new();
+            }
+            let array = &*array;
+            (0..array.len as usize)
+                .map(|i| {
+                    let minutia = *array.pdata.add(i) as *mut libfprint_sys::FpMinutia;
+                    let (mut x, mut y) = (0, 0);
+                    libfprint_sys::fp_minutia_get_coords(minutia, &mut x, &mut y);
+                    FpMinutia { x, y }
+                })
+                .collect()
+        }
+    }
+
+    fn detect_minutiae_async<P: FnOnce(Result<(), FpError>) + 'static>(
+        &self,
+        cancellable: &Cancellable,
+        callback: P,
+    ) {
+        start_async(
+            |cb, data| unsafe {
+                libfprint_sys::fp_image_detect_minutiae(
+                    self.to_glib_none().0,
+                    cancellable.as_ptr().cast(),
+                    cb,
+                    data,
+                )
+            },
+            |image, res| {
+                let mut error = std::ptr::null_mut();
+                let ok = unsafe {
+                    libfprint_sys::fp_image_detect_minutiae_finish(image, res, &mut error)
+                };
+                if ok == glib::ffi::GFALSE {
+                    return Err(unsafe { glib::Error::from_glib_full(error.cast()) }.into());
+                }
+                Ok(())
+            },
+            callback,
+        );
     }
-    fn _detect_minutiae(&self) {
-        unimplemented!()
+
+    #[cfg(not(doctest))]
+    /// Detect the minutiae in the image asynchronously, they can then be retrieved with `FpImage::minutiae`.
+    ///
+    /// The returned future must be driven by the thread default `glib::MainContext`, dropping it cancels the
+    /// detection.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::FpContext;
+    ///
+    /// let ctx = FpContext::new();
+    /// let devices = ctx.devices();
+    /// let dev = devices.get(0).unwrap();
+    ///
+    /// glib::MainContext::default().block_on(async {
+    ///     dev.open().await.unwrap();
+    ///     let image = dev.capture(true).await.unwrap();
+    ///     image.detect_minutiae().await.unwrap();
+    ///     for minutia in image.minutiae() {
+    ///         println!("Minutia at ({}, {})", minutia.x, minutia.y);
+    ///     }
+    /// });
+    /// ```
+    pub fn detect_minutiae(&self) -> FpFuture<()> {
+        Box::pin(gio::GioFuture::new(self, |obj, cancellable, send| {
+            obj.detect_minutiae_async(cancellable, move |res| send.resolve(res));
+        }))
     }
-    fn _detect_minutiae_finish(&self) {
-        unimplemented!()
+
+    /// Detect the minutiae in the image, iterating the thread default `glib::MainContext` until done.
+    pub fn detect_minutiae_sync(&self, cancellable: Option<&Cancellable>) -> Result<(), FpError> {
+        let cancellable = cancellable.cloned().unwrap_or_default();
+        let result = Rc::new(RefCell::new(None));
+
+        let res = result.clone();
+        self.detect_minutiae_async(&cancellable, move |r| *res.borrow_mut() = Some(r));
+
+        let main_context = glib::MainContext::ref_thread_default();
+        loop {
+            if let Some(res) = result.borrow_mut().take() {
+                return res;
+            }
+            main_context.iteration(true);
+        }
     }
     /// Gets the greyscale data for an image.
     pub fn data(&self) -> Vec<u8> {
@@ -72,6 +222,7 @@ impl FpImage {
         }
     }
 
+    /// Gets the binarized data for an image, where ridges are 0. Empty until `FpImage::detect_minutiae` completed.
     pub fn binarized(&self) -> Vec<u8> {
         unsafe {
             let mut len = 0;
@@ -80,4 +231,135 @@ impl FpImage {
             Vec::from_glib_none_num(data, len as usize)
         }
     }
+
+    /// Writes the image as a binary greyscale PGM (`P5`).
+    pub fn write_pgm<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        write_pgm(writer, self.width(), self.height(), &self.data())
+    }
+
+    /// Writes the binarized image as a 1-bit PBM (`P4`), with the ridges in black.
+    ///
+    /// Fails with `std::io::ErrorKind::InvalidInput` if `FpImage::detect_minutiae` did not complete yet.
+    pub fn write_pbm<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        write_pbm(writer, self.width(), self.height(), &self.binarized())
+    }
+
+    /// Encodes the image as an 8-bit greyscale PNG, with its resolution.
+    #[cfg(feature = "png")]
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width(), self.height());
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let ppm = (self.ppmm() * 1000.0).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: ppm,
+            yppu: ppm,
+            unit: png::Unit::Meter,
+        }));
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data())?;
+        writer.finish()?;
+        Ok(png)
+    }
+}
+
+/// A minutia point detected in a `FpImage`.
+///
+/// Only the coordinates are available: `fp_minutia_get_coords` is the only public accessor of libfprint's
+/// `FpMinutia`, which keeps the direction and reliability private.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FpMinutia {
+    /// Horizontal position, in pixels from the left of the image.
+    pub x: i32,
+    /// Vertical position, in pixels from the top of the image.
+    pub y: i32,
+}
+
+/// Writes `data`, one byte per pixel, as a binary greyscale PGM (`P5`).
+fn write_pgm<W: Write>(mut writer: W, width: u32, height: u32, data: &[u8]) -> std::io::Result<()> {
+    write!(writer, "P5\n{} {}\n255\n", width, height)?;
+    writer.write_all(data)
+}
+
+/// Writes `binarized`, one byte per pixel where ridges are 0, as a 1-bit PBM (`P4`).
+fn write_pbm<W: Write>(
+    mut writer: W,
+    width: u32,
+    height: u32,
+    binarized: &[u8],
+) -> std::io::Result<()> {
+    let (width, height) = (width as usize, height as usize);
+    if binarized.len() != width * height {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The image has not been binarized, detect the minutiae first",
+        ));
+    }
+
+    write!(writer, "P4\n{} {}\n", width, height)?;
+    // Each row is packed in bytes, most significant bit first, 1 being black
+    let mut row = vec![0u8; width.div_ceil(8)];
+    for pixels in binarized.chunks(width) {
+        row.fill(0);
+        for (x, _) in pixels.iter().enumerate().filter(|(_, pixel)| **pixel == 0) {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_pbm, write_pgm};
+
+    #[test]
+    fn pgm_header() {
+        let mut pgm = Vec::new();
+        write_pgm(&mut pgm, 3, 2, &[0, 1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(pgm, b"P5\n3 2\n255\n\x00\x01\x02\x03\x04\x05");
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn pgm_round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(13 * 7).collect();
+        let mut pgm = Vec::new();
+        write_pgm(&mut pgm, 13, 7, &data).unwrap();
+        let (width, height, parsed) = crate::testing::parse_pgm(&pgm).unwrap();
+        assert_eq!((width, height, parsed), (13, 7, data.as_slice()));
+    }
+
+    #[test]
+    fn pbm_packing() {
+        // 10 pixels per row take two bytes, the last 6 bits of each row are padding
+        #[rustfmt::skip]
+        let binarized = [
+            0, 255, 255, 255, 255, 255, 255, 255, 0, 255,
+            255, 0, 0, 0, 0, 0, 0, 0, 255, 0,
+        ];
+        let mut pbm = Vec::new();
+        write_pbm(&mut pbm, 10, 2, &binarized).unwrap();
+        assert_eq!(
+            pbm,
+            [
+                b"P4\n10 2\n".as_slice(),
+                &[0b1000_0000, 0b1000_0000, 0b0111_1111, 0b0100_0000]
+            ]
+            .concat()
+        );
+
+        // A single pixel row still takes a whole byte
+        let mut pbm = Vec::new();
+        write_pbm(&mut pbm, 1, 3, &[0, 255, 0]).unwrap();
+        assert_eq!(pbm, [b"P4\n1 3\n".as_slice(), &[0x80, 0x00, 0x80]].concat());
+    }
+
+    #[test]
+    fn pbm_requires_binarized() {
+        let err = write_pbm(Vec::new(), 4, 4, &[]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
 }
@@ -4,10 +4,7 @@
 //!
 //! # Enrolling a new fingerprint
 //! ```rust
-//! use libfprint_rs::{FpContext, FpPrint};
-//!
-//! let context = FpContext::new();
-//! let devices = context.get_devices();
+//! use libfprint_rs::{EnrollTemplate, FpContext, FpEnrollProgress, FpFinger};
 //!
 //! let context = FpContext::new();
 //! let devices = context.devices();
@@ -15,10 +12,12 @@
 //! let dev = devices.get(0).unwrap();
 //! dev.open_sync(None)?;
 //!
-//! let template = FpPrint::new(&dev);
-//! template.set_username("Bruce Banner");
+//! let template = EnrollTemplate::builder(&dev)
+//!     .finger(FpFinger::RightIndex)
+//!     .username("Bruce Banner")
+//!     .build()?;
 //!
-//! let print = dev.enroll_sync(template, None, None, None::<()>)?;
+//! let print = dev.enroll_sync(template, None, None::<FpEnrollProgress<()>>, None)?;
 //! ```
 //! # Verifying a fingerprint
 //! ```rust
@@ -30,36 +29,76 @@
 //!
 //! let enrolled_print = load_print_from_file();
 //!
-//! let match_res = dev.verify_sync(enrolled_print, None, None, None::<()>, None)?;
+//! let match_res = dev.verify_sync(&enrolled_print, None, None::<FpMatchCb<()>>, None, None)?;
+//! ```
+//! # Asynchronous operations
+//! Every operation also has an asynchronous counterpart returning a future. The futures are
+//! driven by the thread default `glib::MainContext`, and dropping one cancels the operation.
+//! ```rust
+//! let context = FpContext::new();
+//! let devices = context.devices();
+//! let dev = devices.get(0).unwrap();
+//!
+//! glib::MainContext::default().block_on(async {
+//!     dev.open().await?;
+//!     let (matched, _scanned) = dev.verify(&enrolled_print, None::<FpMatchCb<()>>, None).await?;
+//!     dev.close().await
+//! })?;
 //! ```
 //! For more examples on how to use this crate, please refer to the [github oficial repository](https://github.com/AlvaroParker/libfprint-rs)
 //! and the [documentation](https://docs.rs/libfprint-rs/latest/libfprint_rs/).
+#[cfg(feature = "serde")]
+mod container;
 mod context;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 mod device;
+#[cfg(feature = "main-loop-thread")]
+mod device_handle;
+mod error;
 mod finger;
 mod image;
+#[cfg(feature = "main-loop-thread")]
+mod main_loop;
 mod print;
+mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 /// Re-export `gio::Cancellable`, it provides a way to cancel sync operations, i.e
 /// `FpDevice::enroll_sync`
 pub use gio::Cancellable;
 /// Re-export `glib::Error`, it provides a way to pass enroll dates to `FpPrint` metadata
 pub use glib::Date as GDate;
-/// Re-export `glib::Error`, it provides error handling for sync operations.
+/// Re-export `glib::Error`, it is passed to the enroll and match callbacks and wrapped by `FpError::Other`.
 pub use glib::Error as GError;
 
-pub use context::FpContext;
-pub use device::{FpDevice, FpEnrollProgress, FpMatchCb};
+#[cfg(feature = "serde")]
+pub use container::{EnrollDate, PrintContainer, PrintMetadata};
+pub use context::{ContextEvent, ContextEvents, FpContext, IdentifyAnyResult};
+pub use device::{
+    DeviceProperty, DeviceWatch, EnrollEvent, EnrollOutcome, EnrollStream, FpDevice,
+    FpDeviceFeature, FpEnrollProgress, FpFingerStatus, FpFuture, FpMatchCb, FpScanType,
+    FpTemperature, Gallery, GalleryMatch,
+};
+#[cfg(feature = "main-loop-thread")]
+pub use device_handle::{DeviceHandle, ImageData};
+pub use error::{FpError, FpRetryReason};
 pub use finger::FpFinger;
-pub use image::FpImage;
-pub use print::FpPrint;
+pub use image::{FpImage, FpMinutia};
+#[cfg(feature = "main-loop-thread")]
+pub use main_loop::MainLoopThread;
+pub use print::{
+    EnrollTemplate, EnrollTemplateBuilder, FpPrint, FpiPrintType, Mismatch, PrintCompatibility,
+};
+pub use store::{FileStore, PrintStore, StoreError, current_user};
 
 #[cfg(test)]
 mod tests {
 
     use std::io::{Read, Write};
 
-    use crate::{FpContext, FpDevice, FpPrint};
+    use crate::{EnrollTemplate, FpContext, FpDevice, FpFinger, FpPrint};
 
     #[test]
     fn get_names() {
@@ -90,15 +129,20 @@ mod tests {
             println!("Not matched");
         }
     }
+    pub fn template(dev: &FpDevice) -> EnrollTemplate {
+        EnrollTemplate::builder(dev)
+            .finger(FpFinger::RightIndex)
+            .username("test")
+            .build()
+            .unwrap()
+    }
     pub fn _enroll_print(dev: &FpDevice) -> FpPrint {
-        let template = FpPrint::new(dev);
-        let print = dev.enroll_sync(template, None, Some(enroll_cb), None);
+        let print = dev.enroll_sync(template(dev), None, Some(enroll_cb), None);
         print.unwrap()
     }
     pub fn save_prints(dev: &FpDevice, id: u32) {
-        let template = FpPrint::new(dev);
         let print = dev
-            .enroll_sync(template, None, Some(enroll_cb), None)
+            .enroll_sync(template(dev), None, Some(enroll_cb), None)
             .unwrap();
         let data = print.serialize().unwrap();
         let name = format!("prints/print{}", id);
@@ -125,7 +169,7 @@ mod tests {
     pub fn match_cb(
         _device: &FpDevice,
         matched_print: Option<FpPrint>,
-        _print: FpPrint,
+        _print: Option<FpPrint>,
         _error: Option<glib::Error>,
         _data: &Option<i32>,
     ) {
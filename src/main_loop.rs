@@ -0,0 +1,154 @@
+//! Dedicated GLib main loop thread, to use libfprint from other async runtimes such as tokio.
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    pin::{Pin, pin},
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    task::Poll,
+    thread::JoinHandle,
+};
+
+use futures_channel::{
+    mpsc::{UnboundedSender, unbounded},
+    oneshot,
+};
+use futures_core::Stream;
+
+use crate::FpContext;
+
+type Job = Box<dyn FnOnce(&FpContext) + Send>;
+
+/// A thread running a GLib main loop, with its own `FpContext`.
+///
+/// libfprint objects are bound to the thread iterating their `glib::MainContext`, so they are created and used on
+/// the thread, through `MainLoopThread::run`. The returned futures are `Send` and do not depend on any runtime, they
+/// can be awaited from tokio or async-std tasks without `spawn_blocking`.
+///
+/// Dropping the `MainLoopThread` stops the main loop, dropping the operations still running on it, and joins the
+/// thread.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FpError, FpMatchCb, FpPrint, MainLoopThread};
+///
+/// // From any async task
+/// async fn verify(main_loop: &MainLoopThread, data: Vec<u8>) -> Result<bool, FpError> {
+///     main_loop
+///         .run(move |ctx| {
+///             let dev = ctx.devices().remove(0);
+///             async move {
+///                 let print = FpPrint::deserialize(&data)?;
+///                 dev.open().await?;
+///                 let (matched, _) = dev.verify(&print, None::<FpMatchCb<()>>, None).await?;
+///                 dev.close().await?;
+///                 Ok(matched)
+///             }
+///         })
+///         .await
+/// }
+///
+/// let main_loop = MainLoopThread::new().unwrap();
+/// ```
+pub struct MainLoopThread {
+    jobs: Option<UnboundedSender<Job>>,
+    thread: Option<JoinHandle<()>>,
+    /// Busy flags of the `DeviceHandle`s, by device id.
+    busy: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl MainLoopThread {
+    /// Spawns the thread and creates its `FpContext`.
+    pub fn new() -> io::Result<Self> {
+        let (jobs, mut receiver) = unbounded::<Job>();
+        let thread = std::thread::Builder::new()
+            .name("libfprint-main-loop".to_string())
+            .spawn(move || {
+                let context = glib::MainContext::new();
+                let main_loop = glib::MainLoop::new(Some(&context), false);
+                context
+                    .with_thread_default(|| {
+                        let fp_context = FpContext::new();
+                        let quit = main_loop.clone();
+                        context.spawn_local(async move {
+                            while let Some(job) =
+                                std::future::poll_fn(|cx| Pin::new(&mut receiver).poll_next(cx))
+                                    .await
+                            {
+                                job(&fp_context);
+                            }
+                            quit.quit();
+                        });
+                        main_loop.run();
+                    })
+                    .expect("the new main context is owned by another thread");
+            })?;
+
+        Ok(Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+            busy: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Runs `f` on the main loop thread, then drives the future it returns there, resolving to its output.
+    ///
+    /// `f` receives the `FpContext` of the thread, from which the devices are obtained. Prints and other libfprint
+    /// objects can not leave the thread, so `R` typically holds serialized prints or plain values. Dropping the
+    /// returned future drops the one of `f`, which cancels the libfprint operation in progress.
+    ///
+    /// # Panics
+    /// The returned future panics if the main loop thread stopped before `f` completed, i.e because `f` panicked.
+    pub fn run<F, Fut, R>(&self, f: F) -> impl Future<Output = R> + Send + use<F, Fut, R>
+    where
+        F: FnOnce(&FpContext) -> Fut + Send + 'static,
+        Fut: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        let (mut sender, receiver) = oneshot::channel();
+        let job: Job = Box::new(move |ctx| {
+            let fut = f(ctx);
+            glib::MainContext::ref_thread_default().spawn_local(async move {
+                let mut fut = pin!(fut);
+                let res = std::future::poll_fn(|cx| {
+                    if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                        return Poll::Ready(Some(res));
+                    }
+                    // The caller dropped its future, drop ours as well to cancel the operation
+                    if sender.poll_canceled(cx).is_ready() {
+                        return Poll::Ready(None);
+                    }
+                    Poll::Pending
+                })
+                .await;
+                if let Some(res) = res {
+                    let _ = sender.send(res);
+                }
+            });
+        });
+        if let Some(jobs) = &self.jobs {
+            // Only fails if the thread stopped, which the receiver reports
+            let _ = jobs.unbounded_send(job);
+        }
+        async move { receiver.await.expect("the main loop thread stopped") }
+    }
+
+    /// The flag set while a `DeviceHandle` request runs on the device `device_id`, shared by every handle on it.
+    pub(crate) fn busy_flag(&self, device_id: &str) -> Arc<AtomicBool> {
+        self.busy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(device_id.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+impl Drop for MainLoopThread {
+    fn drop(&mut self) {
+        // Closing the channel quits the main loop
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
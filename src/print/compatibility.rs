@@ -0,0 +1,173 @@
+use std::fmt::Display;
+
+use glib::{object::ObjectExt, translate::FromGlib};
+
+use crate::device::FpDevice;
+
+use super::FpPrint;
+
+/// Kind of data held by a print, the `fpi-type` property of `FpPrint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FpiPrintType {
+    /// No data yet, i.e a template that was not enrolled.
+    Undefined,
+    /// Opaque data of the device, matched by the device itself.
+    Raw,
+    /// Minutiae extracted from images with NBIS, created by image devices and matched on the host.
+    Nbis,
+    /// Data of a device implementing the Secure Device Connection Protocol, matched by the device itself.
+    Sdcp,
+    /// A type added by a later libfprint, with its raw value.
+    Other(i32),
+}
+
+impl FpiPrintType {
+    /// The type of the prints created by `device`.
+    pub(crate) fn of_device(device: &FpDevice) -> Self {
+        let image_device =
+            unsafe { glib::Type::from_glib(libfprint_sys::fp_image_device_get_type() as usize) };
+        // Not part of the public headers, the type is only registered once an SDCP driver is loaded
+        let sdcp_device = glib::Type::from_name("FpSdcpDevice");
+        if device.type_().is_a(image_device) {
+            FpiPrintType::Nbis
+        } else if sdcp_device.is_some_and(|sdcp_device| device.type_().is_a(sdcp_device)) {
+            FpiPrintType::Sdcp
+        } else {
+            FpiPrintType::Raw
+        }
+    }
+}
+
+impl From<i32> for FpiPrintType {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => FpiPrintType::Undefined,
+            1 => FpiPrintType::Raw,
+            2 => FpiPrintType::Nbis,
+            3 => FpiPrintType::Sdcp,
+            value => FpiPrintType::Other(value),
+        }
+    }
+}
+
+impl Display for FpiPrintType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FpiPrintType::Undefined => write!(f, "undefined"),
+            FpiPrintType::Raw => write!(f, "raw"),
+            FpiPrintType::Nbis => write!(f, "NBIS"),
+            FpiPrintType::Sdcp => write!(f, "SDCP"),
+            FpiPrintType::Other(value) => write!(f, "unknown ({})", value),
+        }
+    }
+}
+
+/// A property that differs between a print and a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch<T> {
+    /// The value of the print.
+    pub print: T,
+    /// The value of the device.
+    pub device: T,
+}
+
+impl<T: PartialEq> Mismatch<T> {
+    fn check(print: T, device: T) -> Option<Self> {
+        (print != device).then_some(Self { print, device })
+    }
+}
+
+/// Why a print is or is not compatible with a device, see `FpPrint::compatibility`.
+///
+/// `FpPrint::compatible` only compares the driver and the device id. The print type is checked as well, a mismatch
+/// means the print was created by a different kind of device, even if the driver name matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintCompatibility {
+    /// Set if the print was created by another driver.
+    pub driver: Option<Mismatch<String>>,
+    /// Set if the print was created by another device of the driver.
+    pub device_id: Option<Mismatch<String>>,
+    /// Set if the print holds data the device can not match. Prints without data never mismatch.
+    pub print_type: Option<Mismatch<FpiPrintType>>,
+}
+
+impl PrintCompatibility {
+    /// Whether no mismatch was found.
+    pub fn is_compatible(&self) -> bool {
+        self.driver.is_none() && self.device_id.is_none() && self.print_type.is_none()
+    }
+}
+
+impl Display for PrintCompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_compatible() {
+            return write!(f, "compatible");
+        }
+        let mut reasons = Vec::new();
+        if let Some(Mismatch { print, device }) = &self.driver {
+            reasons.push(format!(
+                "driver mismatch (print: {print}, device: {device})"
+            ));
+        }
+        if let Some(Mismatch { print, device }) = &self.device_id {
+            reasons.push(format!(
+                "device id mismatch (print: {print}, device: {device})"
+            ));
+        }
+        if let Some(Mismatch { print, device }) = &self.print_type {
+            reasons.push(format!("type mismatch (print: {print}, device: {device})"));
+        }
+        write!(f, "{}", reasons.join(", "))
+    }
+}
+
+impl FpPrint {
+    /// Returns the kind of data the print holds.
+    ///
+    /// `FpiPrintType::Undefined` is also returned if libfprint does not expose the type.
+    pub fn print_type(&self) -> FpiPrintType {
+        if self.find_property("fpi-type").is_none() {
+            return FpiPrintType::Undefined;
+        }
+        let value = self.property_value("fpi-type");
+        match glib::EnumValue::from_value(&value) {
+            Some((_, enum_value)) => FpiPrintType::from(enum_value.value()),
+            None => FpiPrintType::Undefined,
+        }
+    }
+
+    #[cfg(not(doctest))]
+    /// Explains whether the print is compatible with `device`, and why not.
+    /// # Example:
+    /// ```no_run
+    /// let res = print.compatibility(&dev);
+    /// if !res.is_compatible() {
+    ///     println!("Can not migrate {}: {}", print.finger(), res);
+    /// }
+    /// ```
+    pub fn compatibility(&self, device: &FpDevice) -> PrintCompatibility {
+        let print_type = match self.print_type() {
+            // The devices creating the unknown types are unknown as well
+            FpiPrintType::Undefined | FpiPrintType::Other(_) => None,
+            print_type => Mismatch::check(print_type, FpiPrintType::of_device(device)),
+        };
+        PrintCompatibility {
+            driver: Mismatch::check(self.driver(), device.driver()),
+            device_id: Mismatch::check(self.device_id(), device.device_id()),
+            print_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FpiPrintType;
+
+    #[test]
+    fn print_types() {
+        assert_eq!(FpiPrintType::from(2), FpiPrintType::Nbis);
+        assert_eq!(FpiPrintType::from(3), FpiPrintType::Sdcp);
+        assert_eq!(FpiPrintType::from(4), FpiPrintType::Other(4));
+        assert_eq!(FpiPrintType::Other(4).to_string(), "unknown (4)");
+    }
+}
@@ -8,6 +8,12 @@ use glib::{
 
 use crate::{device::FpDevice, finger::FpFinger, image::FpImage};
 
+mod compatibility;
+mod template;
+
+pub use compatibility::{FpiPrintType, Mismatch, PrintCompatibility};
+pub use template::{EnrollTemplate, EnrollTemplateBuilder};
+
 wrapper! {
     /// Struct representing a fingerprint.
     pub struct FpPrint(Object<libfprint_sys::FpPrint, libfprint_sys::FpPrintClass>)
@@ -127,7 +133,7 @@ impl FpPrint {
             );
         }
     }
-    /// Tests whether the prints is compatible with the given device.
+    /// Tests whether the prints is compatible with the given device. See `FpPrint::compatibility` to know why not.
     pub fn compatible(&self, device: &FpDevice) -> bool {
         unsafe {
             libfprint_sys::fp_print_compatible(self.to_glib_none().0, device.to_glib_none().0)
@@ -166,15 +172,15 @@ impl FpPrint {
     /// Deserialize a print definition from permanent storage.
     pub fn deserialize(data: &[u8]) -> Result<FpPrint, glib::Error> {
         let len = data.len();
+        let mut error = std::ptr::null_mut();
         let ptr = unsafe {
             let ptr = glib::translate::ToGlibPtr::to_glib_none(data);
-            let mut error = std::ptr::null_mut();
 
             libfprint_sys::fp_print_deserialize(ptr.0, len.try_into().unwrap(), &mut error)
         };
 
         if ptr.is_null() {
-            Err(unsafe { glib::Error::from_glib_full(ptr.cast()) })
+            Err(unsafe { glib::Error::from_glib_full(error.cast()) })
         } else {
             let print = unsafe { FpPrint::from_glib_full(ptr) };
             unsafe { print.set_data("set", true) };
@@ -0,0 +1,135 @@
+use glib::translate::FromGlib;
+
+use crate::{FpError, GDate, device::FpDevice, finger::FpFinger};
+
+use super::FpPrint;
+
+/// Template of a new print, passed to `FpDevice::enroll_sync` and `FpDevice::enroll`.
+///
+/// Templates are made with `EnrollTemplate::builder`, they always start from a fresh `FpPrint` with validated
+/// metadata, which can only be read afterwards.
+#[derive(Debug, Clone)]
+pub struct EnrollTemplate {
+    print: FpPrint,
+}
+
+impl EnrollTemplate {
+    #[cfg(not(doctest))]
+    /// Starts building a template for `device`.
+    /// # Example:
+    /// ```no_run
+    /// use libfprint_rs::{EnrollTemplate, FpEnrollProgress, FpFinger};
+    ///
+    /// let template = EnrollTemplate::builder(&dev)
+    ///     .finger(FpFinger::RightIndex)
+    ///     .username("bruce")
+    ///     .build()?;
+    /// let print = dev.enroll_sync(template, None, None::<FpEnrollProgress<()>>, None)?;
+    /// ```
+    pub fn builder(device: &FpDevice) -> EnrollTemplateBuilder {
+        EnrollTemplateBuilder {
+            device: device.clone(),
+            finger: FpFinger::Unknown,
+            username: None,
+            description: None,
+            enroll_date: None,
+        }
+    }
+
+    /// The finger to enroll.
+    pub fn finger(&self) -> FpFinger {
+        self.print.finger()
+    }
+
+    /// The user the print belongs to.
+    pub fn username(&self) -> Option<String> {
+        self.print.username()
+    }
+
+    /// The description of the print.
+    pub fn description(&self) -> Option<String> {
+        self.print.description()
+    }
+
+    /// The enroll date of the print.
+    pub fn enroll_date(&self) -> Option<GDate> {
+        self.print.enroll_date()
+    }
+
+    pub(crate) fn into_print(self) -> FpPrint {
+        self.print
+    }
+}
+
+/// Builder of `EnrollTemplate`, see `EnrollTemplate::builder`.
+#[derive(Debug, Clone)]
+pub struct EnrollTemplateBuilder {
+    device: FpDevice,
+    finger: FpFinger,
+    username: Option<String>,
+    description: Option<String>,
+    enroll_date: Option<GDate>,
+}
+
+impl EnrollTemplateBuilder {
+    /// The finger to enroll, required.
+    pub fn finger(mut self, finger: FpFinger) -> Self {
+        self.finger = finger;
+        self
+    }
+
+    /// The user the print belongs to, required.
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// A description of the print.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// The enroll date, today if not set.
+    pub fn enroll_date(mut self, enroll_date: GDate) -> Self {
+        self.enroll_date = Some(enroll_date);
+        self
+    }
+
+    /// Creates the template. Returns `FpError::DataInvalid` if the finger is unknown or the username is missing or
+    /// empty.
+    pub fn build(self) -> Result<EnrollTemplate, FpError> {
+        if self.finger == FpFinger::Unknown {
+            return Err(FpError::DataInvalid(
+                "The finger of the template must be set".to_string(),
+            ));
+        }
+        let username = self
+            .username
+            .filter(|username| !username.is_empty())
+            .ok_or_else(|| {
+                FpError::DataInvalid("The username of the template must be set".to_string())
+            })?;
+
+        let print = FpPrint::new(&self.device);
+        print.set_finger(self.finger);
+        print.set_username(&username);
+        if let Some(description) = &self.description {
+            print.set_description(description);
+        }
+        if let Some(date) = self.enroll_date.or_else(today) {
+            print.set_enroll_date(date);
+        }
+        Ok(EnrollTemplate { print })
+    }
+}
+
+fn today() -> Option<GDate> {
+    let now = glib::DateTime::now_local().ok()?;
+    GDate::from_dmy(
+        now.day_of_month() as u8,
+        unsafe { glib::DateMonth::from_glib(now.month()) },
+        now.year() as u16,
+    )
+    .ok()
+}
@@ -0,0 +1,231 @@
+use std::{
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{FpDevice, FpFinger, FpPrint};
+
+use super::{PrintStore, StoreError};
+
+/// Default location of the prints saved by fprintd.
+const SYSTEM_DIR: &str = "/var/lib/fprint";
+
+/// Print store on the filesystem, using fprintd's layout: `<base>/<user>/<driver>/<device_id>/<finger>`, where
+/// `<finger>` is the hexadecimal value of the `FpFinger` and the file holds the `FpPrint::serialize` data.
+///
+/// Prints are written to a temporary file which is then renamed, so readers never see a partial print. Each
+/// operation also takes a lock on the device directory, shared for reads and exclusive for writes.
+/// # Example:
+/// ```no_run
+/// use libfprint_rs::{FileStore, FpContext, PrintStore};
+///
+/// let ctx = FpContext::new();
+/// let devices = ctx.devices();
+/// let dev = devices.get(0).unwrap();
+///
+/// let store = FileStore::system();
+/// for (user, print) in store.load_all(&dev).unwrap() {
+///     println!("{}: {}", user, print.finger());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    base: PathBuf,
+}
+
+impl FileStore {
+    /// Creates a store rooted at `base`.
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into() }
+    }
+
+    /// The store of the system fprintd, `/var/lib/fprint`. It is usually only accessible by root.
+    pub fn system() -> Self {
+        Self::new(SYSTEM_DIR)
+    }
+
+    /// The root directory of the store.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn device_dir(&self, user: &str, driver: &str, device_id: &str) -> Result<PathBuf, StoreError> {
+        Ok(self
+            .base
+            .join(check_name(user)?)
+            .join(check_name(driver)?)
+            .join(check_name(device_id)?))
+    }
+
+    fn device_dir_of(&self, device: &FpDevice, user: &str) -> Result<PathBuf, StoreError> {
+        self.device_dir(user, &device.driver(), &device.device_id())
+    }
+}
+
+/// Rejects names that would escape their directory.
+fn check_name(name: &str) -> Result<&str, StoreError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(StoreError::InvalidName(name.to_string()));
+    }
+    Ok(name)
+}
+
+fn finger_file(finger: FpFinger) -> String {
+    format!("{:x}", finger as u32)
+}
+
+fn finger_from_file(name: &str) -> Option<FpFinger> {
+    if name.is_empty() || !name.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = i32::from_str_radix(name, 16).ok()?;
+    FpFinger::try_from(value)
+        .ok()
+        .filter(|finger| *finger != FpFinger::Unknown)
+}
+
+/// Locks `dir`, the lock is released when the returned file is dropped.
+fn lock(dir: &Path, exclusive: bool) -> io::Result<File> {
+    let file = File::open(dir)?;
+    if exclusive {
+        file.lock()?;
+    } else {
+        file.lock_shared()?;
+    }
+    Ok(file)
+}
+
+/// Maps a missing file or directory to `None`.
+fn not_found_to_none<T>(res: io::Result<T>) -> io::Result<Option<T>> {
+    match res {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl PrintStore for FileStore {
+    fn save(&self, user: &str, print: &FpPrint) -> Result<(), StoreError> {
+        let finger = print.finger();
+        if finger == FpFinger::Unknown {
+            return Err(StoreError::UnknownFinger);
+        }
+        let dir = self.device_dir(user, &print.driver(), &print.device_id())?;
+        let data = print.serialize()?;
+
+        DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+        let dir_lock = lock(&dir, true)?;
+
+        let name = finger_file(finger);
+        let tmp = dir.join(format!(".{}.tmp", name));
+        let res = (|| {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&tmp)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+            fs::rename(&tmp, dir.join(&name))
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res?;
+        // The rename is only durable once the directory, locked through its own handle, is synced as well
+        dir_lock.sync_all()?;
+        Ok(())
+    }
+
+    fn load(
+        &self,
+        device: &FpDevice,
+        user: &str,
+        finger: FpFinger,
+    ) -> Result<Option<FpPrint>, StoreError> {
+        let dir = self.device_dir_of(device, user)?;
+        let Some(_lock) = not_found_to_none(lock(&dir, false))? else {
+            return Ok(None);
+        };
+        let Some(data) = not_found_to_none(fs::read(dir.join(finger_file(finger))))? else {
+            return Ok(None);
+        };
+
+        let print = FpPrint::deserialize(&data)?;
+        Ok(print.compatible(device).then_some(print))
+    }
+
+    fn delete(&self, device: &FpDevice, user: &str, finger: FpFinger) -> Result<(), StoreError> {
+        let dir = self.device_dir_of(device, user)?;
+        let Some(_lock) = not_found_to_none(lock(&dir, true))? else {
+            return Ok(());
+        };
+        not_found_to_none(fs::remove_file(dir.join(finger_file(finger))))?;
+        Ok(())
+    }
+
+    fn fingers(&self, device: &FpDevice, user: &str) -> Result<Vec<FpFinger>, StoreError> {
+        let dir = self.device_dir_of(device, user)?;
+        let Some(_lock) = not_found_to_none(lock(&dir, false))? else {
+            return Ok(Vec::new());
+        };
+
+        let mut fingers = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if let Some(finger) = entry.file_name().to_str().and_then(finger_from_file) {
+                fingers.push(finger);
+            }
+        }
+        fingers.sort();
+        Ok(fingers)
+    }
+
+    fn users(&self) -> Result<Vec<String>, StoreError> {
+        let Some(entries) = not_found_to_none(fs::read_dir(&self.base))? else {
+            return Ok(Vec::new());
+        };
+
+        let mut users = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Ok(user) = entry.file_name().into_string()
+                && check_name(&user).is_ok()
+            {
+                users.push(user);
+            }
+        }
+        users.sort();
+        Ok(users)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_name, finger_file, finger_from_file};
+    use crate::FpFinger;
+
+    #[test]
+    fn finger_file_names() {
+        assert_eq!(finger_file(FpFinger::RightIndex), "7");
+        assert_eq!(finger_file(FpFinger::RightLittle), "a");
+        assert_eq!(finger_from_file("a"), Some(FpFinger::RightLittle));
+        assert_eq!(finger_from_file("0"), None);
+        assert_eq!(finger_from_file(".7.tmp"), None);
+        assert_eq!(finger_from_file("+7"), None);
+    }
+
+    #[test]
+    fn reject_escaping_names() {
+        assert!(check_name("bruce").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("..").is_err());
+        assert!(check_name("../etc").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Persistent storage of enrolled prints.
+mod file;
+mod user;
+
+use std::fmt::Display;
+
+use crate::{FpDevice, FpError, FpFinger, FpPrint};
+
+pub use file::FileStore;
+pub use user::current_user;
+#[cfg(feature = "dbus")]
+pub(crate) use user::user_name;
+
+/// Storage of the enrolled prints of each user, indexed by device and finger.
+///
+/// `FileStore` implements it using the same layout as fprintd.
+pub trait PrintStore {
+    /// Saves `print` as the print of its finger for `user`, replacing the previous one.
+    fn save(&self, user: &str, print: &FpPrint) -> Result<(), StoreError>;
+    /// Loads the print of `finger` enrolled by `user` on `device`, `None` if there is none or if it is not compatible
+    /// with `device`.
+    fn load(
+        &self,
+        device: &FpDevice,
+        user: &str,
+        finger: FpFinger,
+    ) -> Result<Option<FpPrint>, StoreError>;
+    /// Deletes the print of `finger` enrolled by `user` on `device`. Deleting a missing print is not an error.
+    fn delete(&self, device: &FpDevice, user: &str, finger: FpFinger) -> Result<(), StoreError>;
+    /// Lists the fingers enrolled by `user` on `device`.
+    fn fingers(&self, device: &FpDevice, user: &str) -> Result<Vec<FpFinger>, StoreError>;
+    /// Lists the users with stored prints, on any device.
+    fn users(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Loads all the prints enrolled by `user` that are compatible with `device`.
+    fn load_user(&self, device: &FpDevice, user: &str) -> Result<Vec<FpPrint>, StoreError> {
+        let mut prints = Vec::new();
+        for finger in self.fingers(device, user)? {
+            prints.extend(self.load(device, user, finger)?);
+        }
+        Ok(prints)
+    }
+    /// Loads the prints of all the users that are compatible with `device`, alongside their user.
+    fn load_all(&self, device: &FpDevice) -> Result<Vec<(String, FpPrint)>, StoreError> {
+        let mut prints = Vec::new();
+        for user in self.users()? {
+            for print in self.load_user(device, &user)? {
+                prints.push((user.clone(), print));
+            }
+        }
+        Ok(prints)
+    }
+}
+
+/// Errors returned by a `PrintStore`.
+#[derive(Debug)]
+pub enum StoreError {
+    /// Reading or writing the storage failed.
+    Io(std::io::Error),
+    /// A stored print could not be serialized or deserialized.
+    Print(FpError),
+    /// A user, driver or device ID can not be used as a storage key.
+    InvalidName(String),
+    /// The print to save has no finger set.
+    UnknownFinger,
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Io(error) => write!(f, "{}", error),
+            StoreError::Print(error) => write!(f, "{}", error),
+            StoreError::InvalidName(name) => write!(f, "Invalid storage name {:?}", name),
+            StoreError::UnknownFinger => write!(f, "The print has no finger set"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Io(error) => Some(error),
+            StoreError::Print(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(error: std::io::Error) -> Self {
+        StoreError::Io(error)
+    }
+}
+
+impl From<FpError> for StoreError {
+    fn from(error: FpError) -> Self {
+        StoreError::Print(error)
+    }
+}
+
+impl From<glib::Error> for StoreError {
+    fn from(error: glib::Error) -> Self {
+        StoreError::Print(error.into())
+    }
+}
@@ -0,0 +1,49 @@
+/// Name of the effective user, the owner of the prints enrolled by the process unless another user is given.
+pub fn current_user() -> Option<String> {
+    user_name(unsafe { libc::geteuid() })
+}
+
+/// Name of the user `uid`, looked up through NSS so that LDAP, sssd or systemd-homed accounts are found as well.
+pub(crate) fn user_name(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        let mut passwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+        let mut result = std::ptr::null_mut();
+        let res = unsafe {
+            libc::getpwuid_r(
+                uid,
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match res {
+            // The buffer is too small for the entry
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() => {
+                let name = unsafe { std::ffi::CStr::from_ptr((*result).pw_name) };
+                return Some(name.to_string_lossy().into_owned());
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_user, user_name};
+
+    #[test]
+    fn user_names() {
+        // Compared with the non-reentrant lookup, since the accounts depend on the NSS setup of the host
+        let uid = unsafe { libc::geteuid() };
+        let expected = unsafe { libc::getpwuid(uid).as_ref() }.map(|passwd| {
+            unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) }
+                .to_string_lossy()
+                .into_owned()
+        });
+        assert_eq!(user_name(uid), expected);
+        assert_eq!(current_user(), expected);
+    }
+}
@@ -0,0 +1,421 @@
+//! Helpers to exercise applications without fingerprint hardware, using libfprint's virtual drivers.
+//!
+//! libfprint ships the `virtual_image`, `virtual_device` and `virtual_device_storage` drivers, which are only
+//! enumerated when the `FP_VIRTUAL_IMAGE`, `FP_VIRTUAL_DEVICE` and `FP_VIRTUAL_DEVICE_STORAGE` environment variables
+//! point to a Unix socket path. Once the device is opened, the driver listens on that socket and a test script can
+//! drive it: place fingers, send images, or inject retries and errors.
+//!
+//! `VirtualContext` sets up the environment and the `FpContext`, while `VirtualImage` and `VirtualDevice` send the
+//! commands. The controllers are `Send`, so they can be moved to another thread while the main thread is blocked in a
+//! sync operation.
+//!
+//! This module is only available with the `testing` feature, and requires libfprint to be built with the virtual
+//! drivers. The crate's own tests skip themselves without the drivers, unless the `FP_VIRTUAL_REQUIRED` environment
+//! variable is set, as in CI.
+//! # Example:
+//! ```no_run
+//! use libfprint_rs::{EnrollTemplate, FpEnrollProgress, FpFinger, FpMatchCb, testing::VirtualContext};
+//!
+//! let virt = VirtualContext::new().unwrap();
+//! let dev = virt.device().unwrap();
+//! let controller = virt.device_controller();
+//! dev.open_sync(None).unwrap();
+//!
+//! for _ in 0..dev.nr_enroll_stage() {
+//!     controller.scan("right-index").unwrap();
+//! }
+//! let template = EnrollTemplate::builder(&dev)
+//!     .finger(FpFinger::RightIndex)
+//!     .username("bruce")
+//!     .build()
+//!     .unwrap();
+//! let print = dev
+//!     .enroll_sync(template, None, None::<FpEnrollProgress<()>>, None)
+//!     .unwrap();
+//!
+//! controller.scan("right-index").unwrap();
+//! let matched = dev
+//!     .verify_sync(&print, None, None::<FpMatchCb<()>>, None, None)
+//!     .unwrap();
+//! assert!(matched);
+//! ```
+use std::{
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{FpContext, FpDevice, FpError, FpRetryReason, FpScanType};
+
+const IMAGE_ENV: &str = "FP_VIRTUAL_IMAGE";
+const DEVICE_ENV: &str = "FP_VIRTUAL_DEVICE";
+const STORAGE_ENV: &str = "FP_VIRTUAL_DEVICE_STORAGE";
+
+const IMAGE_SOCKET: &str = "virtual_image.socket";
+const DEVICE_SOCKET: &str = "virtual_device.socket";
+const STORAGE_SOCKET: &str = "virtual_device_storage.socket";
+
+/// How long the controllers wait for the driver to start listening.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serializes the access to the environment, and the use of the virtual drivers, in the whole process.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A `FpContext` bound to the virtual drivers.
+///
+/// Only one `VirtualContext` exists at a time in the process: `VirtualContext::new` blocks until the previous one is
+/// dropped. This keeps tests running in parallel from sharing the environment variables and the sockets.
+pub struct VirtualContext {
+    context: FpContext,
+    dir: PathBuf,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl VirtualContext {
+    /// Creates a new context, with the virtual drivers listening on sockets inside a new temporary directory.
+    pub fn new() -> io::Result<Self> {
+        let lock = ENVIRONMENT.lock().unwrap_or_else(PoisonError::into_inner);
+        let dir = std::env::temp_dir().join(format!(
+            "libfprint-rs-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+
+        // Safety: the variables are only written while holding `ENVIRONMENT`, and libfprint reads them during the
+        // enumeration below.
+        unsafe {
+            std::env::set_var(IMAGE_ENV, dir.join(IMAGE_SOCKET));
+            std::env::set_var(DEVICE_ENV, dir.join(DEVICE_SOCKET));
+            std::env::set_var(STORAGE_ENV, dir.join(STORAGE_SOCKET));
+        }
+        let context = FpContext::new();
+        context.enumerate();
+
+        Ok(Self {
+            context,
+            dir,
+            _lock: lock,
+        })
+    }
+
+    /// The underlying context. Its devices also include any physical reader connected to the system.
+    pub fn context(&self) -> &FpContext {
+        &self.context
+    }
+
+    /// The `virtual_image` device, an image based device driven by `VirtualImage`.
+    pub fn image_device(&self) -> Option<FpDevice> {
+        self.find_device("virtual_image")
+    }
+
+    /// The `virtual_device` device, a match-on-chip like device driven by `VirtualDevice`.
+    pub fn device(&self) -> Option<FpDevice> {
+        self.find_device("virtual_device")
+    }
+
+    /// The `virtual_device_storage` device, like `VirtualContext::device` but with an on-device storage.
+    pub fn storage_device(&self) -> Option<FpDevice> {
+        self.find_device("virtual_device_storage")
+    }
+
+    /// The controller of `VirtualContext::image_device`.
+    pub fn image_controller(&self) -> VirtualImage {
+        VirtualImage::new(self.dir.join(IMAGE_SOCKET))
+    }
+
+    /// The controller of `VirtualContext::device`.
+    pub fn device_controller(&self) -> VirtualDevice {
+        VirtualDevice::new(self.dir.join(DEVICE_SOCKET))
+    }
+
+    /// The controller of `VirtualContext::storage_device`.
+    pub fn storage_controller(&self) -> VirtualDevice {
+        VirtualDevice::new(self.dir.join(STORAGE_SOCKET))
+    }
+
+    fn find_device(&self, driver: &str) -> Option<FpDevice> {
+        self.context
+            .devices()
+            .into_iter()
+            .find(|device| device.driver() == driver)
+    }
+}
+
+impl Drop for VirtualContext {
+    fn drop(&mut self) {
+        // Safety: `ENVIRONMENT` is still held, it is released once the fields are dropped.
+        unsafe {
+            std::env::remove_var(IMAGE_ENV);
+            std::env::remove_var(DEVICE_ENV);
+            std::env::remove_var(STORAGE_ENV);
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Connects to a driver socket, waiting for the driver to listen on it, i.e until the device is opened.
+fn connect(path: &Path) -> io::Result<UnixStream> {
+    let start = Instant::now();
+    loop {
+        match UnixStream::connect(path) {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+                ) && start.elapsed() < CONNECT_TIMEOUT =>
+            {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            res => return res,
+        }
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Controller of the `virtual_image` driver.
+///
+/// The driver reads messages made of a native endian `i32` width and height, followed by the greyscale pixels. A
+/// negative width is a command instead of an image, with the height as its argument.
+pub struct VirtualImage {
+    path: PathBuf,
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl VirtualImage {
+    /// Creates a controller for the driver listening on `path`, i.e the value of `FP_VIRTUAL_IMAGE`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            stream: Mutex::new(None),
+        }
+    }
+
+    /// The socket path of the driver.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sends a message over a connection kept open between messages, a new connection drops the previous one and
+    /// any message the driver did not read yet.
+    fn send(&self, header: [i32; 2], data: &[u8]) -> io::Result<()> {
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+        let connection = match stream.as_mut() {
+            Some(connection) => connection,
+            None => stream.insert(connect(&self.path)?),
+        };
+
+        let mut message = Vec::with_capacity(8 + data.len());
+        message.extend_from_slice(&header[0].to_ne_bytes());
+        message.extend_from_slice(&header[1].to_ne_bytes());
+        message.extend_from_slice(data);
+        let res = connection.write_all(&message);
+        if res.is_err() {
+            *stream = None;
+        }
+        res
+    }
+
+    /// Sends a greyscale image of `width` x `height` pixels, one byte per pixel.
+    pub fn send_image(&self, width: u32, height: u32, data: &[u8]) -> io::Result<()> {
+        // The driver rejects images bigger than 5000 pixels in any dimension
+        if width == 0 || height == 0 || width > 5000 || height > 5000 {
+            return Err(invalid_input("Invalid image size"));
+        }
+        if data.len() != width as usize * height as usize {
+            return Err(invalid_input("Image data does not match its size"));
+        }
+        self.send([width as i32, height as i32], data)
+    }
+
+    /// Sends a binary (`P5`) PGM image with a maximum value of at most 255.
+    pub fn send_pgm(&self, pgm: &[u8]) -> io::Result<()> {
+        let (width, height, data) = parse_pgm(pgm)?;
+        self.send_image(width, height, data)
+    }
+
+    /// Makes the current scan fail with a retry error.
+    pub fn retry(&self, reason: FpRetryReason) -> io::Result<()> {
+        self.send([-1, reason.code() as i32], &[])
+    }
+
+    /// Makes the current operation fail with `error`, which must be one of the device errors.
+    pub fn error(&self, error: &FpError) -> io::Result<()> {
+        let code = error
+            .device_error_code()
+            .ok_or_else(|| invalid_input("Not a device error"))?;
+        self.send([-2, code as i32], &[])
+    }
+
+    /// Whether a finger is automatically reported on the sensor for each image, and removed afterwards. Otherwise
+    /// the finger status must be reported with `VirtualImage::report_finger`.
+    pub fn set_automatic_finger(&self, enabled: bool) -> io::Result<()> {
+        self.send([-3, enabled as i32], &[])
+    }
+
+    /// Reports whether a finger is present on the sensor.
+    pub fn report_finger(&self, present: bool) -> io::Result<()> {
+        self.send([-4, present as i32], &[])
+    }
+
+    /// Makes the device disappear, as if it was unplugged.
+    pub fn remove(&self) -> io::Result<()> {
+        self.send([-5, 0], &[])
+    }
+}
+
+/// Parses a binary PGM image, returning its width, height and pixels.
+pub(crate) fn parse_pgm(pgm: &[u8]) -> io::Result<(u32, u32, &[u8])> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid binary PGM image");
+    if !pgm.starts_with(b"P5") {
+        return Err(invalid());
+    }
+
+    let mut pos = 2;
+    let mut fields = [0u32; 3];
+    for field in &mut fields {
+        // Skip the whitespaces and comments before the field
+        loop {
+            match pgm.get(pos) {
+                Some(b'#') => {
+                    while pgm.get(pos).is_some_and(|&c| c != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => pos += 1,
+                Some(_) => break,
+                None => return Err(invalid()),
+            }
+        }
+        let start = pos;
+        while pgm.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        *field = std::str::from_utf8(&pgm[start..pos])
+            .ok()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)?;
+    }
+
+    let [width, height, maxval] = fields;
+    if maxval == 0 || maxval > 255 {
+        return Err(invalid());
+    }
+    // A single whitespace separates the header from the pixels
+    if !pgm.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        return Err(invalid());
+    }
+    let len = width as usize * height as usize;
+    let data = pgm.get(pos + 1..pos + 1 + len).ok_or_else(invalid)?;
+    Ok((width, height, data))
+}
+
+/// Controller of the `virtual_device` and `virtual_device_storage` drivers.
+///
+/// The driver reads one text command per connection. Commands are queued by the driver, so they can be sent before
+/// starting the operation that consumes them.
+#[derive(Debug, Clone)]
+pub struct VirtualDevice {
+    path: PathBuf,
+}
+
+impl VirtualDevice {
+    /// Creates a controller for the driver listening on `path`, i.e the value of `FP_VIRTUAL_DEVICE`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The socket path of the driver.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sends a raw command, i.e `SCAN right-index`.
+    pub fn command(&self, command: &str) -> io::Result<()> {
+        let mut stream = connect(&self.path)?;
+        stream.write_all(command.as_bytes())
+    }
+
+    /// Scans the finger identified by `id`. Scanning the same `id` during enroll and verify results in a match.
+    pub fn scan(&self, id: &str) -> io::Result<()> {
+        self.command(&format!("SCAN {}", id))
+    }
+
+    /// Makes the next scan fail with a retry error.
+    pub fn retry(&self, reason: FpRetryReason) -> io::Result<()> {
+        self.command(&format!("RETRY {}", reason.code()))
+    }
+
+    /// Makes the next operation fail with `error`, which must be one of the device errors.
+    pub fn error(&self, error: &FpError) -> io::Result<()> {
+        let code = error
+            .device_error_code()
+            .ok_or_else(|| invalid_input("Not a device error"))?;
+        self.command(&format!("ERROR {}", code))
+    }
+
+    /// Reports whether a finger is present on the sensor.
+    pub fn report_finger(&self, present: bool) -> io::Result<()> {
+        self.command(&format!("FINGER {}", present as i32))
+    }
+
+    /// Sets the number of stages needed to enroll a print.
+    pub fn set_enroll_stages(&self, stages: u32) -> io::Result<()> {
+        self.command(&format!("SET_ENROLL_STAGES {}", stages))
+    }
+
+    /// Sets the scan type reported by the device.
+    pub fn set_scan_type(&self, scan_type: FpScanType) -> io::Result<()> {
+        let scan_type = match scan_type {
+            FpScanType::Swipe => "swipe",
+            FpScanType::Press => "press",
+        };
+        self.command(&format!("SET_SCAN_TYPE {}", scan_type))
+    }
+
+    /// Delays the processing of the next command.
+    pub fn sleep(&self, duration: Duration) -> io::Result<()> {
+        self.command(&format!("SLEEP {}", duration.as_millis()))
+    }
+
+    /// Adds a print identified by `id` to the device storage.
+    pub fn insert(&self, id: &str) -> io::Result<()> {
+        self.command(&format!("INSERT {}", id))
+    }
+
+    /// Removes the print identified by `id` from the device storage.
+    pub fn remove(&self, id: &str) -> io::Result<()> {
+        self.command(&format!("REMOVE {}", id))
+    }
+
+    /// Makes the device disappear, as if it was unplugged.
+    pub fn unplug(&self) -> io::Result<()> {
+        self.command("UNPLUG")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pgm;
+
+    #[test]
+    fn parse_binary_pgm() {
+        let pgm = b"P5\n# created by a test\n3 2\n255\n\x00\x01\x02\x03\x04\x05";
+        let (width, height, data) = parse_pgm(pgm).unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(data, &[0, 1, 2, 3, 4, 5]);
+
+        assert!(parse_pgm(b"P2\n3 2\n255\n0 1 2 3 4 5").is_err());
+        assert!(parse_pgm(b"P5\n3 2\n255\n\x00\x01").is_err());
+        assert!(parse_pgm(b"P5\n3 2\n65535\n\x00\x01\x02\x03\x04\x05").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gio::{DBusCallFlags, DBusConnection, DBusConnectionFlags, DBusSignalFlags, prelude::*};
+use glib::{Variant, variant::ObjectPath};
+use libfprint_rs::{FileStore, dbus::FprintService, testing::VirtualContext};
+
+const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+
+/// Skips the test if libfprint was built without the virtual drivers, unless `FP_VIRTUAL_REQUIRED` is set, as in CI.
+macro_rules! device_or_skip {
+    ($device:expr) => {
+        match $device {
+            Some(device) => device,
+            None => {
+                assert!(
+                    std::env::var_os("FP_VIRTUAL_REQUIRED").is_none(),
+                    "libfprint was built without the virtual drivers"
+                );
+                eprintln!("libfprint was built without the virtual drivers, skipping");
+                return;
+            }
+        }
+    };
+}
+
+/// A client of the service on a private bus.
+struct Client {
+    connection: DBusConnection,
+    service: String,
+    path: String,
+    signals: Rc<RefCell<Vec<(String, Variant)>>>,
+}
+
+impl Client {
+    fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: Option<Variant>,
+    ) -> Result<Variant, glib::Error> {
+        glib::MainContext::default().block_on(self.connection.call_future(
+            Some(&self.service),
+            path,
+            interface,
+            method,
+            args.as_ref(),
+            None,
+            DBusCallFlags::NONE,
+            -1,
+        ))
+    }
+
+    fn device(&self, method: &str, args: Option<Variant>) -> Result<Variant, glib::Error> {
+        self.call(&self.path, DEVICE_INTERFACE, method, args)
+    }
+
+    /// Runs the main context until `signal` is emitted with `done` set, returning the results of all its emissions.
+    fn wait_done(&self, signal: &str) -> Vec<String> {
+        let ctx = glib::MainContext::default();
+        loop {
+            let results: Vec<(String, bool)> = self
+                .signals
+                .borrow()
+                .iter()
+                .filter(|(name, _)| name == signal)
+                .map(|(_, args)| args.get().unwrap())
+                .collect();
+            if results.last().is_some_and(|(_, done)| *done) {
+                self.signals.borrow_mut().clear();
+                return results.into_iter().map(|(result, _)| result).collect();
+            }
+            ctx.iteration(true);
+        }
+    }
+}
+
+#[test]
+fn enroll_verify_delete() {
+    if glib::find_program_in_path("dbus-daemon").is_none() {
+        eprintln!("dbus-daemon is not available, skipping");
+        return;
+    }
+    let virt = VirtualContext::new().unwrap();
+    let dev = device_or_skip!(virt.device());
+    let controller = virt.device_controller();
+    let index = virt
+        .context()
+        .devices()
+        .iter()
+        .position(|d| d.device_id() == dev.device_id())
+        .unwrap();
+
+    let bus = gio::TestDBus::new(gio::TestDBusFlags::NONE);
+    bus.up();
+    let address = bus.bus_address().unwrap();
+    let connect = || {
+        DBusConnection::for_address_sync(
+            &address,
+            DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        )
+        .unwrap()
+    };
+
+    let store = std::env::temp_dir().join(format!("libfprint-rs-dbus-{}", std::process::id()));
+    let service_connection = connect();
+    let service =
+        FprintService::register(&service_connection, virt.context(), FileStore::new(&store))
+            .unwrap();
+
+    let connection = connect();
+    let signals = Rc::new(RefCell::new(Vec::new()));
+    let _subscription = connection.subscribe_to_signal(
+        None,
+        Some(DEVICE_INTERFACE),
+        None,
+        None,
+        None,
+        DBusSignalFlags::NONE,
+        {
+            let signals = signals.clone();
+            move |signal| {
+                signals
+                    .borrow_mut()
+                    .push((signal.signal_name.to_string(), signal.parameters.clone()))
+            }
+        },
+    );
+    let client = Client {
+        connection,
+        service: service_connection.unique_name().unwrap().to_string(),
+        path: service.device_paths()[index].clone(),
+        signals,
+    };
+
+    let (devices,): (Vec<ObjectPath>,) = client
+        .call(
+            FprintService::MANAGER_PATH,
+            "net.reactivated.Fprint.Manager",
+            "GetDevices",
+            None,
+        )
+        .unwrap()
+        .get()
+        .unwrap();
+    assert!(devices.iter().any(|path| path.as_str() == client.path));
+
+    let err = client
+        .device("EnrollStart", Some(("right-index-finger",).to_variant()))
+        .unwrap_err();
+    assert!(err.message().contains("not claimed"));
+
+    client.device("Claim", Some(("",).to_variant())).unwrap();
+
+    for _ in 0..dev.nr_enroll_stage() {
+        controller.scan("right-index").unwrap();
+    }
+    client
+        .device("EnrollStart", Some(("right-index-finger",).to_variant()))
+        .unwrap();
+    let results = client.wait_done("EnrollStatus");
+    assert_eq!(results.last().unwrap(), "enroll-completed");
+    client.device("EnrollStop", None).unwrap();
+
+    let (fingers,): (Vec<String>,) = client
+        .device("ListEnrolledFingers", Some(("",).to_variant()))
+        .unwrap()
+        .get()
+        .unwrap();
+    assert_eq!(fingers, ["right-index-finger"]);
+
+    controller.scan("right-index").unwrap();
+    client
+        .device("VerifyStart", Some(("any",).to_variant()))
+        .unwrap();
+    assert_eq!(
+        client.wait_done("VerifyStatus").last().unwrap(),
+        "verify-match"
+    );
+    client.device("VerifyStop", None).unwrap();
+
+    controller.scan("left-index").unwrap();
+    client
+        .device("VerifyStart", Some(("any",).to_variant()))
+        .unwrap();
+    assert_eq!(
+        client.wait_done("VerifyStatus").last().unwrap(),
+        "verify-no-match"
+    );
+    client.device("VerifyStop", None).unwrap();
+
+    client.device("DeleteEnrolledFingers2", None).unwrap();
+    let err = client
+        .device("ListEnrolledFingers", Some(("",).to_variant()))
+        .unwrap_err();
+    assert!(err.message().contains("No fingerprints enrolled"));
+
+    // Claiming right after a release waits for the device to be closed
+    client.connection.call(
+        Some(&client.service),
+        &client.path,
+        DEVICE_INTERFACE,
+        "Release",
+        None,
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+        |res| assert!(res.is_ok()),
+    );
+    client.device("Claim", Some(("",).to_variant())).unwrap();
+    client.device("Release", None).unwrap();
+    drop(service);
+    bus.down();
+    let _ = std::fs::remove_dir_all(store);
+}
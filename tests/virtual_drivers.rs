@@ -0,0 +1,465 @@
+use libfprint_rs::{
+    EnrollOutcome, EnrollTemplate, FpDevice, FpDeviceFeature, FpEnrollProgress, FpError, FpFinger,
+    FpFingerStatus, FpMatchCb, FpPrint, FpRetryReason, FpiPrintType, Gallery, Mismatch,
+    testing::{VirtualContext, VirtualDevice},
+};
+
+/// Skips the test if libfprint was built without the virtual drivers, unless `FP_VIRTUAL_REQUIRED` is set, as in CI.
+macro_rules! device_or_skip {
+    ($device:expr) => {
+        match $device {
+            Some(device) => device,
+            None => {
+                assert!(
+                    std::env::var_os("FP_VIRTUAL_REQUIRED").is_none(),
+                    "libfprint was built without the virtual drivers"
+                );
+                eprintln!("libfprint was built without the virtual drivers, skipping");
+                return;
+            }
+        }
+    };
+}
+
+/// Opens the virtual device with single stage enrollments, `None` without the virtual drivers.
+fn open_virtual(virt: &VirtualContext) -> Option<(FpDevice, VirtualDevice)> {
+    Some(open(virt.device()?, virt.device_controller()))
+}
+
+/// Opens the virtual storage device with single stage enrollments and an empty storage, `None` without the
+/// virtual drivers.
+fn open_storage(virt: &VirtualContext) -> Option<(FpDevice, VirtualDevice)> {
+    let (dev, controller) = open(virt.storage_device()?, virt.storage_controller());
+    dev.clear_storage_sync(None).unwrap();
+    Some((dev, controller))
+}
+
+fn open(dev: FpDevice, controller: VirtualDevice) -> (FpDevice, VirtualDevice) {
+    dev.open_sync(None).unwrap();
+    controller.set_enroll_stages(1).unwrap();
+    (dev, controller)
+}
+
+/// Template of `finger` for a test user.
+fn template(dev: &FpDevice, finger: FpFinger) -> EnrollTemplate {
+    EnrollTemplate::builder(dev)
+        .finger(finger)
+        .username("bruce")
+        .build()
+        .unwrap()
+}
+
+/// Enrolls `finger`, scanned as the virtual print named after it, e.g. `right-index`.
+fn enroll_one(dev: &FpDevice, controller: &VirtualDevice, finger: FpFinger) -> FpPrint {
+    controller
+        .scan(&finger.to_string().to_lowercase().replace(' ', "-"))
+        .unwrap();
+    dev.enroll_sync(
+        template(dev, finger),
+        None,
+        None::<FpEnrollProgress<()>>,
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn enroll_verify_identify() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    let prints = [FpFinger::RightIndex, FpFinger::LeftIndex]
+        .map(|finger| enroll_one(&dev, &controller, finger));
+
+    controller.scan("right-index").unwrap();
+    let matched = dev
+        .verify_sync(&prints[0], None, None::<FpMatchCb<()>>, None, None)
+        .unwrap();
+    assert!(matched);
+
+    controller.scan("left-index").unwrap();
+    let matched = dev
+        .verify_sync(&prints[0], None, None::<FpMatchCb<()>>, None, None)
+        .unwrap();
+    assert!(!matched);
+
+    controller.scan("left-index").unwrap();
+    let matched = dev
+        .identify_sync(&prints, None, None::<FpMatchCb<()>>, None, None)
+        .unwrap();
+    assert!(matched.unwrap().equal(&prints[1]));
+
+    controller.scan("right-thumb").unwrap();
+    let matched = dev
+        .identify_sync(&prints, None, None::<FpMatchCb<()>>, None, None)
+        .unwrap();
+    assert!(matched.is_none());
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn retries_and_errors() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+
+    controller.retry(FpRetryReason::TooShort).unwrap();
+    let res = dev.verify_sync(&print, None, None::<FpMatchCb<()>>, None, None);
+    assert!(matches!(res, Err(FpError::RetryTooShort(_))));
+
+    controller.error(&FpError::Proto(String::new())).unwrap();
+    let res = dev.verify_sync(&print, None, None::<FpMatchCb<()>>, None, None);
+    assert!(matches!(res, Err(FpError::Proto(_))));
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn match_callback_on_retry() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let match_cb = move |_: &FpDevice,
+                         matched: Option<FpPrint>,
+                         scanned: Option<FpPrint>,
+                         error: Option<glib::Error>,
+                         _: &Option<()>| {
+        tx.send((matched.is_some(), scanned.is_some(), error.is_some()))
+            .unwrap();
+    };
+    controller.retry(FpRetryReason::TooShort).unwrap();
+    let res = dev.verify_sync(&print, None, Some(match_cb.clone()), None, None);
+    assert!(matches!(res, Err(FpError::RetryTooShort(_))));
+    // No scanned print comes with a retry
+    assert_eq!(rx.try_recv().unwrap(), (false, false, true));
+
+    controller.retry(FpRetryReason::CenterFinger).unwrap();
+    let res = dev.identify_sync(&[print], None, Some(match_cb), None, None);
+    assert!(matches!(res, Err(FpError::RetryCenterFinger(_))));
+    assert_eq!(rx.try_recv().unwrap(), (false, false, true));
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn storage() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_storage(&virt));
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+
+    let stored = dev.list_prints_sync(None).unwrap();
+    assert_eq!(stored.len(), 1);
+    assert!(stored[0].equal(&print));
+
+    dev.delete_print_sync(&print, None).unwrap();
+    assert!(dev.list_prints_sync(None).unwrap().is_empty());
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn update_print() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+    let print = FpPrint::deserialize(&print.serialize().unwrap()).unwrap();
+
+    controller.scan("right-index").unwrap();
+    let res = dev.update_print_sync(&print, None, None::<FpEnrollProgress<()>>, None);
+    if dev.has_feature(FpDeviceFeature::UPDATE_PRINT) {
+        assert!(res.unwrap().equal(&print));
+    } else {
+        assert!(matches!(res, Err(FpError::NotSupported(_))));
+    }
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn enroll_checked() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    controller.scan("right-index").unwrap();
+    let outcome = dev
+        .enroll_checked_sync(
+            template(&dev, FpFinger::RightIndex),
+            &[],
+            None,
+            None::<FpEnrollProgress<()>>,
+            None,
+        )
+        .unwrap();
+    let EnrollOutcome::Enrolled(print) = outcome else {
+        panic!("expected a new print, got {:?}", outcome);
+    };
+
+    let gallery = [print];
+    controller.scan("right-index").unwrap();
+    let outcome = dev
+        .enroll_checked_sync(
+            template(&dev, FpFinger::RightIndex),
+            &gallery,
+            None,
+            None::<FpEnrollProgress<()>>,
+            None,
+        )
+        .unwrap();
+    match outcome {
+        EnrollOutcome::Duplicate { existing } => {
+            assert!(!dev.has_feature(FpDeviceFeature::DUPLICATES_CHECK));
+            assert!(existing.equal(&gallery[0]));
+        }
+        EnrollOutcome::DuplicateOnDevice => {
+            assert!(dev.has_feature(FpDeviceFeature::DUPLICATES_CHECK));
+        }
+        EnrollOutcome::Enrolled(_) => panic!("the finger was enrolled twice"),
+    }
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn identify_in_gallery() {
+    let virt = VirtualContext::new().unwrap();
+    let storage = device_or_skip!(virt.storage_device());
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+    // Enrolled on another reader
+    let gallery: Gallery<u32> = [(1000, print), (1001, FpPrint::new(&storage))]
+        .into_iter()
+        .collect();
+
+    controller.scan("right-index").unwrap();
+    let res = dev.identify_in_sync(&gallery, None).unwrap();
+    assert_eq!((res.key, res.skipped), (Some(1000), 1));
+
+    controller.scan("left-index").unwrap();
+    let res = dev.identify_in_sync(&gallery, None).unwrap();
+    assert_eq!((res.key, res.skipped), (None, 1));
+
+    let incompatible: Gallery<u32> = [(1001, FpPrint::new(&storage))].into_iter().collect();
+    assert!(matches!(
+        dev.identify_in_sync(&incompatible, None),
+        Err(FpError::DataNotFound(_))
+    ));
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn print_compatibility() {
+    let virt = VirtualContext::new().unwrap();
+    let image_dev = device_or_skip!(virt.image_device());
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    assert_eq!(FpPrint::new(&dev).print_type(), FpiPrintType::Undefined);
+    let print = enroll_one(&dev, &controller, FpFinger::RightIndex);
+    assert_eq!(print.print_type(), FpiPrintType::Raw);
+    assert!(print.compatibility(&dev).is_compatible());
+
+    let res = print.compatibility(&image_dev);
+    assert!(!print.compatible(&image_dev));
+    assert_eq!(
+        res.driver,
+        Some(Mismatch {
+            print: "virtual_device".to_string(),
+            device: "virtual_image".to_string(),
+        })
+    );
+    assert!(res.device_id.is_some());
+    assert_eq!(
+        res.print_type,
+        Some(Mismatch {
+            print: FpiPrintType::Raw,
+            device: FpiPrintType::Nbis,
+        })
+    );
+    assert!(res.to_string().contains("type mismatch"));
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn enroll_template_builder() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+
+    let missing_finger = EnrollTemplate::builder(&dev).username("bruce").build();
+    assert!(matches!(missing_finger, Err(FpError::DataInvalid(_))));
+    let empty_username = EnrollTemplate::builder(&dev)
+        .finger(FpFinger::RightIndex)
+        .username("")
+        .build();
+    assert!(matches!(empty_username, Err(FpError::DataInvalid(_))));
+
+    let template = EnrollTemplate::builder(&dev)
+        .finger(FpFinger::RightIndex)
+        .username("bruce")
+        .description("Right index")
+        .build()
+        .unwrap();
+    controller.scan("right-index").unwrap();
+    let print = dev
+        .enroll_sync(template, None, None::<FpEnrollProgress<()>>, None)
+        .unwrap();
+    assert_eq!(print.finger(), FpFinger::RightIndex);
+    assert_eq!(print.username().as_deref(), Some("bruce"));
+    assert_eq!(print.description().as_deref(), Some("Right index"));
+    assert!(print.enroll_date().is_some_and(|date| date.valid()));
+
+    dev.close_sync(None).unwrap();
+}
+
+#[test]
+fn identify_any() {
+    let virt = VirtualContext::new().unwrap();
+    let (dev, controller) = device_or_skip!(open_virtual(&virt));
+    let (storage, storage_controller) = device_or_skip!(open_storage(&virt));
+
+    let prints = [(&dev, &controller), (&storage, &storage_controller)]
+        .map(|(dev, controller)| enroll_one(dev, controller, FpFinger::RightIndex));
+
+    storage_controller.scan("right-index").unwrap();
+    let (matched_dev, matched, _) = virt.context().identify_any_sync(&prints, None).unwrap();
+    assert_eq!(matched_dev.device_id(), storage.device_id());
+    assert!(matched.unwrap().equal(&prints[1]));
+
+    controller.scan("left-index").unwrap();
+    let (matched_dev, matched, _) = virt.context().identify_any_sync(&prints, None).unwrap();
+    assert_eq!(matched_dev.device_id(), dev.device_id());
+    assert!(matched.is_none());
+
+    // A bad swipe on one reader does not stop the other one
+    controller.retry(FpRetryReason::TooShort).unwrap();
+    storage_controller.scan("right-index").unwrap();
+    let (matched_dev, matched, _) = virt.context().identify_any_sync(&prints, None).unwrap();
+    assert_eq!(matched_dev.device_id(), storage.device_id());
+    assert!(matched.unwrap().equal(&prints[1]));
+
+    // Nor the reader itself, which waits for the finger again
+    controller.retry(FpRetryReason::CenterFinger).unwrap();
+    controller.scan("right-index").unwrap();
+    let (matched_dev, matched, _) = virt.context().identify_any_sync(&prints, None).unwrap();
+    assert_eq!(matched_dev.device_id(), dev.device_id());
+    assert!(matched.unwrap().equal(&prints[0]));
+
+    dev.close_sync(None).unwrap();
+    storage.close_sync(None).unwrap();
+}
+
+#[cfg(feature = "main-loop-thread")]
+#[test]
+fn main_loop_thread() {
+    let virt = VirtualContext::new().unwrap();
+    device_or_skip!(virt.device());
+    let controller = virt.device_controller();
+    let main_loop = libfprint_rs::MainLoopThread::new().unwrap();
+
+    controller.set_enroll_stages(1).unwrap();
+    controller.scan("right-index").unwrap();
+    let enroll = main_loop.run(|ctx| {
+        let dev = ctx
+            .devices()
+            .into_iter()
+            .find(|dev| dev.driver() == "virtual_device")
+            .unwrap();
+        async move {
+            dev.open().await?;
+            let print = dev
+                .enroll(
+                    template(&dev, FpFinger::RightIndex),
+                    None::<FpEnrollProgress<()>>,
+                    None,
+                )
+                .await?;
+            dev.close().await?;
+            print.serialize().map_err(FpError::from)
+        }
+    });
+    // Awaited from another thread, without iterating the main context of the device
+    let data = std::thread::spawn(move || glib::MainContext::new().block_on(enroll))
+        .join()
+        .unwrap()
+        .unwrap();
+    assert!(FpPrint::deserialize(&data).is_ok());
+}
+
+#[cfg(feature = "main-loop-thread")]
+#[test]
+fn device_handle() {
+    use libfprint_rs::{DeviceHandle, FpFinger, MainLoopThread};
+    use std::sync::Arc;
+
+    // Awaits from another thread, without iterating the main context of the device
+    fn block_on<R: Send + 'static>(fut: impl Future<Output = R> + Send + 'static) -> R {
+        std::thread::spawn(move || glib::MainContext::new().block_on(fut))
+            .join()
+            .unwrap()
+    }
+
+    let virt = VirtualContext::new().unwrap();
+    device_or_skip!(virt.device());
+    let controller = virt.device_controller();
+    let main_loop = Arc::new(MainLoopThread::new().unwrap());
+    let dev = block_on(DeviceHandle::list(&main_loop))
+        .into_iter()
+        .find(|dev| dev.driver() == "virtual_device")
+        .unwrap();
+
+    block_on(dev.open()).unwrap();
+    controller.set_enroll_stages(1).unwrap();
+    controller.scan("right-index").unwrap();
+    let print = block_on(dev.enroll(FpFinger::RightIndex, "bruce".to_string(), None)).unwrap();
+
+    // The device waits for a scan, other requests are rejected meanwhile
+    let verify = dev.verify(print.clone());
+    assert!(dev.is_busy());
+    assert!(matches!(
+        block_on(dev.clone().verify(print.clone())),
+        Err(FpError::Busy(_))
+    ));
+    // Including through the handles of another listing
+    let other = block_on(DeviceHandle::list(&main_loop))
+        .into_iter()
+        .find(|other| other.device_id() == dev.device_id())
+        .unwrap();
+    assert!(other.is_busy());
+    assert!(matches!(
+        block_on(other.verify(print)),
+        Err(FpError::Busy(_))
+    ));
+    controller.scan("right-index").unwrap();
+    assert!(block_on(verify).unwrap());
+    assert!(!dev.is_busy());
+
+    block_on(dev.close()).unwrap();
+}
+
+#[test]
+fn capture_image() {
+    let virt = VirtualContext::new().unwrap();
+    let dev = device_or_skip!(virt.image_device());
+    let controller = virt.image_controller();
+    dev.open_sync(None).unwrap();
+
+    let (width, height) = (64, 48);
+    let pixels: Vec<u8> = (0..width * height).map(|i| (i % 256) as u8).collect();
+    controller.set_automatic_finger(true).unwrap();
+    // Only send the image once the device waits for a finger, it is dropped otherwise
+    dev.connect_finger_status_notify(move |dev| {
+        if dev.finger_status().contains(FpFingerStatus::NEEDED) {
+            controller.send_image(width, height, &pixels).unwrap();
+        }
+    });
+
+    let image = dev.capture_sync(true, None).unwrap();
+    assert_eq!((image.width(), image.height()), (width, height));
+
+    dev.close_sync(None).unwrap();
+}